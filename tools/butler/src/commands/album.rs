@@ -0,0 +1,16 @@
+use super::util::labels::LabelCommand;
+use crate::prelude::*;
+use clap::Args;
+
+// Albums are exported by `generate` to albums.bin, tags stay in butler.
+#[derive(Args)]
+pub(super) struct Album {
+    #[clap(subcommand)]
+    command: LabelCommand,
+}
+
+impl Album {
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        self.command.run(db, Label::Album)
+    }
+}
@@ -0,0 +1,48 @@
+use super::util::display::print_captions;
+use crate::prelude::*;
+use clap::Args;
+
+#[derive(Args)]
+pub(super) struct Caption {
+    #[clap(help = "Photos to caption, e.g. 42 or 'album:Wedding & date:2021-05-03'")]
+    target: PhotoFilter,
+    #[clap(help = "The caption, the current ones are shown if omitted")]
+    text: Option<String>,
+    #[clap(long, conflicts_with = "text", help = "Remove the caption")]
+    clear: bool,
+}
+
+impl Caption {
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        let mut pt = db.photos()?;
+
+        let pids = pt.select(Some(&self.target))?;
+        if pids.is_empty() {
+            bail!("no photo matches the query");
+        }
+        let caption = match (self.text, self.clear) {
+            (Some(text), _) => Some(text.trim().to_owned()),
+            (None, true) => None,
+            (None, false) => {
+                print_captions(pt.records_of(pids));
+                return Ok(());
+            }
+        };
+        match &caption {
+            Some(text) if text.is_empty() => bail!("empty caption, use --clear to remove it"),
+            Some(text) => validate_caption(text)?,
+            None => {}
+        }
+
+        for pid in pids {
+            let caption = caption.clone();
+            pt.entry(pid)
+                .modify()
+                .unwrap()
+                .set_overrides_with(|overrides| overrides.caption = caption)
+                .commit();
+        }
+        pt.finalize()?;
+        Ok(())
+    }
+}
@@ -7,20 +7,36 @@ use clap::{ArgGroup, Args};
         .arg("select")
         .conflicts_with("unselect")
 ))]
+#[clap(group(
+    ArgGroup::new("favoring")
+        .arg("favorite")
+        .conflicts_with("unfavorite")
+))]
 pub(super) struct Commit {
-    queries: Vec<PhotoQuery>,
+    #[clap(help = "Queries like 100..200 or 'mount:sd & !status:committed'")]
+    queries: Vec<PhotoFilter>,
     #[clap(short, long)]
     select: bool,
     #[clap(short, long)]
     unselect: bool,
+    #[clap(
+        short,
+        long,
+        parse(try_from_str = parse_rating),
+        help = "Rate 0 to 5 stars, 0 for unrated"
+    )]
+    rating: Option<u8>,
+    #[clap(long)]
+    favorite: bool,
+    #[clap(long)]
+    unfavorite: bool,
     #[clap(short, long)]
     quiet: bool,
 }
 
 impl Commit {
-    pub(super) fn run(self) -> Result<()> {
-        let mut pt = pt_access_mut();
-        pt.initialize(DEFAULT_PHOTOS_DB_PATH)?;
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        let mut pt = db.photos()?;
 
         let select_request = match (self.select, self.unselect) {
             (true, true) => unreachable!(),
@@ -28,18 +44,26 @@ impl Commit {
             (false, true) => Some(false),
             _ => None,
         };
+        let favorite_request = match (self.favorite, self.unfavorite) {
+            (true, true) => unreachable!(),
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            _ => None,
+        };
 
-        for query in self.queries.into_iter() {
-            let mut rec = unwrap_some_or!(pt.entry(query).modify().ok(), { continue });
+        for pid in pt.select(&self.queries)? {
+            let mut rec = unwrap_some_or!(pt.entry(pid).modify().ok(), { continue });
 
             select_request.map(|sel| rec.set_selected(sel));
+            self.rating.map(|rating| rec.set_rating(rating));
+            favorite_request.map(|fav| rec.set_favorite(fav));
             rec.with_status(Committed).commit();
         }
 
         if !self.quiet {
             pt.display_list(None)?;
         }
-        pt.finalize(DEFAULT_PHOTOS_DB_PATH)?;
+        pt.finalize()?;
         Ok(())
     }
 }
@@ -0,0 +1,278 @@
+use super::util::prompt::confirm;
+use crate::prelude::*;
+use clap::{ArgEnum, Args, Subcommand};
+
+mod rows;
+use rows::{MountPointRow, PhotoRow};
+
+#[derive(Args)]
+pub(super) struct Db {
+    #[clap(subcommand)]
+    command: DbCommand,
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    #[clap(about = "Dump the tables as JSON or CSV")]
+    Export(Export),
+    #[clap(about = "Replace the tables with an export")]
+    Import(Import),
+}
+
+#[derive(ArgEnum, Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(ArgEnum, Clone, Copy, PartialEq)]
+enum Tables {
+    All,
+    Photos,
+    Mounts,
+}
+
+impl Tables {
+    fn photos(self) -> bool {
+        self != Tables::Mounts
+    }
+    fn mounts(self) -> bool {
+        self != Tables::Photos
+    }
+}
+
+// guesses from the file extension if not given, JSON by default
+fn format_of(format: Option<Format>, path: Option<&Path>) -> Format {
+    format.unwrap_or_else(|| match path.and_then(Path::extension) {
+        Some(ext) if ext.eq_ignore_ascii_case("csv") => Format::Csv,
+        _ => Format::Json,
+    })
+}
+
+// records by reference when exporting, owned when importing
+#[derive(Serialize, Deserialize)]
+struct Dump<M, P> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mountpoints: Option<Vec<M>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    photos: Option<Vec<P>>,
+    // the next PID, missing from CSV and from older exports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    counter: Option<PID>,
+}
+
+#[derive(Args)]
+struct Export {
+    #[clap(short, long, arg_enum, default_value = "all")]
+    tables: Tables,
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        help = "Guessed from the output file if omitted"
+    )]
+    format: Option<Format>,
+    #[clap(short, long, help = "Write to this file instead of stdout")]
+    output: Option<PathBuf>,
+}
+
+impl Export {
+    fn run(self, db: &mut Database) -> Result<()> {
+        let format = format_of(self.format, self.output.as_deref());
+        if format == Format::Csv && self.tables == Tables::All {
+            bail!("CSV holds one table only, specify --tables photos or --tables mounts");
+        }
+        let writer: Box<dyn Write> = match &self.output {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(std::io::stdout()),
+        };
+        let writer = BufWriter::new(writer);
+
+        // cloned, as loading the photos takes the database
+        let mountpoints = self
+            .tables
+            .mounts()
+            .then(|| db.mounts().records().cloned().collect::<Vec<_>>());
+        let pt = match self.tables.photos() {
+            true => Some(db.photos()?),
+            false => None,
+        };
+        match format {
+            Format::Json => {
+                let dump = Dump {
+                    mountpoints,
+                    photos: pt.as_ref().map(|pt| pt.records().collect()),
+                    counter: pt.as_ref().map(|pt| pt.counter()),
+                };
+                serde_json::to_writer_pretty(writer, &dump)?;
+            }
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(writer);
+                if let Some(pt) = &pt {
+                    for rec in pt.records() {
+                        writer.serialize(PhotoRow::from(rec))?;
+                    }
+                } else {
+                    for mp in mountpoints.iter().flatten() {
+                        writer.serialize(MountPointRow::from(mp))?;
+                    }
+                }
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct Import {
+    input: PathBuf,
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        help = "Table in a CSV file, all tables found in a JSON file if omitted"
+    )]
+    tables: Option<Tables>,
+    #[clap(short, long, arg_enum, help = "Guessed from the input file if omitted")]
+    format: Option<Format>,
+    #[clap(short, long, help = "Replace without asking")]
+    yes: bool,
+}
+
+impl Import {
+    fn read(&self) -> Result<Dump<MountPointRecord, PhotoRecord>> {
+        let reader = BufReader::new(File::open(&self.input)?);
+        let mut dump: Dump<_, _> = match format_of(self.format, Some(&self.input)) {
+            Format::Json => serde_json::from_reader(reader)?,
+            Format::Csv => {
+                let mut reader = csv::Reader::from_reader(reader);
+                match self.tables {
+                    Some(Tables::Photos) => Dump {
+                        photos: Some(
+                            reader
+                                .deserialize::<PhotoRow>()
+                                .map(|row| row?.try_into())
+                                .collect::<Result<_>>()?,
+                        ),
+                        mountpoints: None,
+                        counter: None,
+                    },
+                    Some(Tables::Mounts) => Dump {
+                        photos: None,
+                        mountpoints: Some(
+                            reader
+                                .deserialize::<MountPointRow>()
+                                .map(|row| row?.try_into())
+                                .collect::<Result<_>>()?,
+                        ),
+                        counter: None,
+                    },
+                    _ => bail!("specify --tables photos or --tables mounts for CSV"),
+                }
+            }
+        };
+        match self.tables {
+            Some(Tables::Photos) => dump.mountpoints = None,
+            Some(Tables::Mounts) => {
+                dump.photos = None;
+                dump.counter = None;
+            }
+            _ => {}
+        }
+        Ok(dump)
+    }
+
+    fn run(self, db: &mut Database) -> Result<()> {
+        let dump = self.read()?;
+        let mpids = match &dump.mountpoints {
+            Some(mps) => mps.iter().map(|mp| mp.uuid).collect::<HashSet<_>>(),
+            None => db.mounts().records().map(|mp| mp.uuid).collect(),
+        };
+        if let Some(mps) = &dump.mountpoints {
+            let mut aliases = HashSet::new();
+            for mp in mps.iter() {
+                if mps.iter().filter(|x| x.path == mp.path).count() > 1 {
+                    bail!("{} is listed twice", mp.path);
+                }
+                if let Some(alias) = &mp.alias {
+                    validate_alias(alias)?;
+                    if !aliases.insert(alias) {
+                        bail!("alias {} is listed twice", alias);
+                    }
+                }
+            }
+        }
+        if let Some(photos) = &dump.photos {
+            for rec in photos.iter() {
+                validate_photo(rec).with_context(|| format!("invalid photo {}", rec.pid))?;
+            }
+        }
+        // the photos kept, if only mount points are replaced, are checked too
+        let mut pt = db.photos()?;
+        let orphans = match &dump.photos {
+            Some(photos) => photos
+                .iter()
+                .filter(|rec| !mpids.contains(&rec.location.mpid))
+                .count(),
+            None => pt
+                .records()
+                .filter(|rec| !mpids.contains(&rec.location.mpid))
+                .count(),
+        };
+        if orphans > 0 {
+            bail!("{} photos are on unknown mount points", orphans);
+        }
+
+        let question = format!(
+            "Replace with {} mount points and {} photos?",
+            dump.mountpoints.as_ref().map(Vec::len).unwrap_or(0),
+            dump.photos.as_ref().map(Vec::len).unwrap_or(0)
+        );
+        if !self.yes && !confirm(&question)? {
+            return Ok(());
+        }
+
+        // photos first, so that a duplicate PID leaves both tables untouched
+        if let Some(photos) = dump.photos {
+            // clearing keeps the counter, so PIDs given out before stay used
+            pt.clear();
+            for rec in photos {
+                pt.restore(rec)?;
+            }
+            pt.raise_counter(dump.counter.unwrap_or(0));
+            pt.finalize()?;
+        }
+        if let Some(mps) = dump.mountpoints {
+            let mut mpt = db.mounts_mut();
+            mpt.clear();
+            for mp in mps {
+                mpt.insert(mp);
+            }
+        }
+        Ok(())
+    }
+}
+
+// as the commands setting these fields do
+fn validate_photo(rec: &PhotoRecord) -> Result<()> {
+    if rec.rating > MAX_RATING {
+        bail!("invalid rating {}, expect 0 to {}", rec.rating, MAX_RATING);
+    }
+    for name in rec.tags.iter().chain(rec.albums.iter()) {
+        validate_label(name)?;
+    }
+    if let Some(caption) = &rec.overrides.caption {
+        validate_caption(caption)?;
+    }
+    Ok(())
+}
+
+impl Db {
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        match self.command {
+            DbCommand::Export(x) => x.run(db),
+            DbCommand::Import(x) => x.run(db),
+        }
+    }
+}
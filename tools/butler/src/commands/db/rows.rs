@@ -0,0 +1,200 @@
+use crate::prelude::*;
+
+// flat forms of the records, as CSV has no nesting
+
+// labels cannot contain commas, see validate_label()
+fn join_labels(names: &BTreeSet<String>) -> String {
+    names.iter().cloned().collect::<Vec<_>>().join(",")
+}
+
+fn split_labels(s: &str) -> BTreeSet<String> {
+    s.split(',')
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+// exposure times like "1/125"
+fn parse_fraction(s: &str) -> Result<(u32, u32)> {
+    let (num, denom) = s
+        .split_once('/')
+        .ok_or_else(|| anyhow!("expect a fraction like 1/125, got {}", s))?;
+    Ok((num.parse()?, denom.parse()?))
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct PhotoRow {
+    pid: PID,
+    mpid: Uuid,
+    filename: PathBuf,
+    file_hash: FileHash,
+    ctime: DateTime<Utc>,
+    mtime: DateTime<Utc>,
+    file_length: u64,
+    etime: Option<DateTime<Utc>>,
+    width: u32,
+    height: u32,
+    orientation: PhotoOrientation,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    altitude: Option<f64>,
+    make: Option<String>,
+    model: Option<String>,
+    lens: Option<String>,
+    focal_length: Option<u32>,
+    iso: Option<u32>,
+    exposure_time: Option<String>,
+    f_number: Option<u32>,
+    phash: Option<u64>,
+    sharpness: Option<u32>,
+    etime_shift: i64,
+    override_etime: Option<DateTime<Utc>>,
+    override_orientation: Option<PhotoOrientation>,
+    caption: Option<String>,
+    tags: String,
+    albums: String,
+    rating: u8,
+    favorite: bool,
+    selected: bool,
+    status: PhotoRecordStatus,
+    commit_time: Option<DateTime<Utc>>,
+}
+
+impl From<&PhotoRecord> for PhotoRow {
+    fn from(rec: &PhotoRecord) -> Self {
+        let meta = &rec.metadata;
+        Self {
+            pid: rec.pid,
+            mpid: rec.location.mpid,
+            filename: rec.location.filename.to_path_buf(),
+            file_hash: rec.file_hash,
+            ctime: meta.ctime,
+            mtime: meta.mtime,
+            file_length: meta.file_length,
+            etime: meta.etime,
+            width: meta.width,
+            height: meta.height,
+            orientation: meta.orientation,
+            latitude: meta.gps.map(|gps| gps.latitude()),
+            longitude: meta.gps.map(|gps| gps.longitude()),
+            altitude: meta.gps.and_then(|gps| gps.altitude()),
+            make: meta.camera.make.clone(),
+            model: meta.camera.model.clone(),
+            lens: meta.camera.lens.clone(),
+            focal_length: meta.camera.focal_length,
+            iso: meta.camera.iso,
+            exposure_time: meta
+                .camera
+                .exposure_time
+                .map(|(num, denom)| format!("{}/{}", num, denom)),
+            f_number: meta.camera.f_number,
+            phash: rec.fingerprint.as_ref().map(|x| x.phash),
+            sharpness: rec.fingerprint.as_ref().map(|x| x.sharpness),
+            etime_shift: rec.etime_shift,
+            override_etime: rec.overrides.etime,
+            override_orientation: rec.overrides.orientation,
+            caption: rec.overrides.caption.clone(),
+            tags: join_labels(&rec.tags),
+            albums: join_labels(&rec.albums),
+            rating: rec.rating,
+            favorite: rec.favorite,
+            selected: rec.selected,
+            status: rec.status,
+            commit_time: rec.commit_time,
+        }
+    }
+}
+
+impl TryFrom<PhotoRow> for PhotoRecord {
+    type Error = anyhow::Error;
+
+    fn try_from(row: PhotoRow) -> Result<Self> {
+        Ok(Self {
+            pid: row.pid,
+            location: FileLocation::new(row.mpid, row.filename).into(),
+            file_hash: row.file_hash,
+            metadata: PhotoMetadata {
+                ctime: row.ctime,
+                mtime: row.mtime,
+                file_length: row.file_length,
+                etime: row.etime,
+                width: row.width,
+                height: row.height,
+                orientation: row.orientation,
+                gps: row
+                    .latitude
+                    .zip(row.longitude)
+                    .and_then(|(lat, lon)| GeoPosition::from_degrees(lat, lon, row.altitude)),
+                camera: CameraInfo {
+                    make: row.make,
+                    model: row.model,
+                    lens: row.lens,
+                    focal_length: row.focal_length,
+                    iso: row.iso,
+                    exposure_time: row
+                        .exposure_time
+                        .as_deref()
+                        .map(parse_fraction)
+                        .transpose()?,
+                    f_number: row.f_number,
+                },
+            },
+            fingerprint: row
+                .phash
+                .zip(row.sharpness)
+                .map(|(phash, sharpness)| PhotoFingerprint { phash, sharpness }),
+            etime_shift: row.etime_shift,
+            overrides: PhotoOverrides {
+                etime: row.override_etime,
+                orientation: row.override_orientation,
+                caption: row.caption,
+            },
+            tags: split_labels(&row.tags),
+            albums: split_labels(&row.albums),
+            rating: row.rating,
+            favorite: row.favorite,
+            selected: row.selected,
+            status: row.status,
+            commit_time: row.commit_time,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct MountPointRow {
+    mpid: Uuid,
+    path: PathBuf,
+    alias: Option<String>,
+    timezone: String,
+    offline: bool,
+    volume_id: Option<Uuid>,
+}
+
+impl From<&MountPointRecord> for MountPointRow {
+    fn from(mp: &MountPointRecord) -> Self {
+        Self {
+            mpid: mp.uuid,
+            path: mp.path.to_path_buf(),
+            alias: mp.alias.clone(),
+            timezone: mp.utc_offset.to_string(),
+            offline: mp.offline,
+            volume_id: mp.volume_id,
+        }
+    }
+}
+
+impl TryFrom<MountPointRow> for MountPointRecord {
+    type Error = anyhow::Error;
+
+    fn try_from(row: MountPointRow) -> Result<Self> {
+        Ok(Self {
+            uuid: row.mpid,
+            path: CanonicalizedPath::new_unchecked(row.path),
+            alias: row.alias,
+            utc_offset: row.timezone.parse()?,
+            offline: row.offline,
+            volume_id: row.volume_id,
+            rescan: false,
+        })
+    }
+}
@@ -0,0 +1,137 @@
+use crate::config::FacesConfig;
+use crate::prelude::*;
+use image::DynamicImage;
+
+// what the detector finds in a photo, as (box, score, embedding)
+pub type FoundFace = (FaceBox, f32, Vec<f32>);
+
+#[cfg(feature = "faces")]
+mod onnx {
+    use super::*;
+    use image::imageops::FilterType;
+    use image::RgbImage;
+    use tract_onnx::prelude::*;
+
+    // an UltraFace-like detector, taking 320x240 RGB and giving scores of
+    // shape [1, N, 2] and corners of shape [1, N, 4], in fractions
+    const DETECTOR_SIZE: (u32, u32) = (320, 240);
+    // an ArcFace-like embedder, taking 112x112 RGB and giving [1, D]
+    const EMBEDDER_SIZE: u32 = 112;
+    const IOU_THRESHOLD: f32 = 0.3;
+
+    type Model = TypedSimplePlan<TypedModel>;
+
+    pub struct Detector {
+        detector: Model,
+        embedder: Model,
+        min_score: f32,
+    }
+
+    fn load_model(path: &Path, (w, h): (u32, u32)) -> Result<Model> {
+        let shape = tvec!(1, 3, h as usize, w as usize);
+        tract_onnx::onnx()
+            .model_for_path(path)
+            .and_then(|model| {
+                model.with_input_fact(0, InferenceFact::dt_shape(f32::datum_type(), shape))
+            })
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .with_context(|| format!("Error loading {}", path.display()))
+    }
+
+    fn to_tensor(img: &RgbImage, mean: f32, std: f32) -> Tensor {
+        let (w, h) = img.dimensions();
+        tract_ndarray::Array4::from_shape_fn((1, 3, h as usize, w as usize), |(_, c, y, x)| {
+            (img.get_pixel(x as u32, y as u32)[c] as f32 - mean) / std
+        })
+        .into()
+    }
+
+    fn iou(x: &[f32; 4], y: &[f32; 4]) -> f32 {
+        let area = |b: &[f32; 4]| (b[2] - b[0]).max(0.) * (b[3] - b[1]).max(0.);
+        let inter = area(&[
+            x[0].max(y[0]),
+            x[1].max(y[1]),
+            x[2].min(y[2]),
+            x[3].min(y[3]),
+        ]);
+        inter / (area(x) + area(y) - inter)
+    }
+
+    impl Detector {
+        pub fn load(config: &FacesConfig) -> Result<Self> {
+            Ok(Self {
+                detector: load_model(&config.detector, DETECTOR_SIZE)?,
+                embedder: load_model(&config.embedder, (EMBEDDER_SIZE, EMBEDDER_SIZE))?,
+                min_score: config.min_score,
+            })
+        }
+
+        pub fn detect(&self, img: &DynamicImage) -> Result<Vec<FoundFace>> {
+            let (w, h) = DETECTOR_SIZE;
+            let resized = img.resize_exact(w, h, FilterType::Triangle).to_rgb8();
+            let outputs = self.detector.run(tvec!(to_tensor(&resized, 127., 128.)))?;
+            let scores = outputs[0].to_array_view::<f32>()?;
+            let corners = outputs[1].to_array_view::<f32>()?;
+
+            let mut candidates = (0..scores.shape()[1])
+                .map(|i| {
+                    let corner = |j: usize| corners[[0, i, j]].clamp(0., 1.);
+                    (
+                        scores[[0, i, 1]],
+                        [corner(0), corner(1), corner(2), corner(3)],
+                    )
+                })
+                .filter(|(score, _)| *score >= self.min_score)
+                .collect::<Vec<_>>();
+            candidates.sort_by(|x, y| y.0.total_cmp(&x.0));
+            // non-maximum suppression
+            let mut kept: Vec<(f32, [f32; 4])> = vec![];
+            for (score, corners) in candidates {
+                if kept.iter().all(|(_, k)| iou(k, &corners) < IOU_THRESHOLD) {
+                    kept.push((score, corners));
+                }
+            }
+
+            let (iw, ih) = (img.width() as f32, img.height() as f32);
+            let mut faces = vec![];
+            for (score, [x0, y0, x1, y1]) in kept {
+                let (x, y) = ((x0 * iw) as u32, (y0 * ih) as u32);
+                let (cw, ch) = (((x1 - x0) * iw) as u32, ((y1 - y0) * ih) as u32);
+                if cw == 0 || ch == 0 {
+                    continue;
+                }
+                let face = img
+                    .crop_imm(x, y, cw, ch)
+                    .resize_exact(EMBEDDER_SIZE, EMBEDDER_SIZE, FilterType::Triangle)
+                    .to_rgb8();
+                let outputs = self.embedder.run(tvec!(to_tensor(&face, 127.5, 128.)))?;
+                let bbox = FaceBox {
+                    left: x0,
+                    top: y0,
+                    width: x1 - x0,
+                    height: y1 - y0,
+                };
+                faces.push((bbox, score, outputs[0].as_slice::<f32>()?.to_vec()));
+            }
+            Ok(faces)
+        }
+    }
+}
+
+#[cfg(feature = "faces")]
+pub use onnx::Detector;
+
+#[cfg(not(feature = "faces"))]
+pub struct Detector;
+
+#[cfg(not(feature = "faces"))]
+impl Detector {
+    pub fn load(_config: &FacesConfig) -> Result<Self> {
+        bail!("butler is built without face detection, rebuild it with `--features faces`")
+    }
+
+    pub fn detect(&self, _img: &DynamicImage) -> Result<Vec<FoundFace>> {
+        unreachable!()
+    }
+}
@@ -0,0 +1,94 @@
+use crate::config::config;
+use crate::prelude::*;
+use crate::util::progress::bar;
+use clap::Args;
+use image::io::Reader as ImageReader;
+
+mod detector;
+use detector::Detector;
+
+#[derive(Args)]
+pub(super) struct Faces {
+    #[clap(help = "Queries like 'selected' or 'album:Family', all photos if omitted")]
+    queries: Vec<PhotoFilter>,
+    #[clap(short, long, help = "Scan again the photos scanned before")]
+    force: bool,
+}
+
+impl Faces {
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        let faces_config = unwrap_some_or!(&config().faces, {
+            bail!("no face models in config.json, see notes/faces.md")
+        });
+        let detector = Detector::load(faces_config)?;
+
+        let pt = db.photos()?;
+        let mut store = FaceStore::load()?;
+        let online = pt
+            .mounts()
+            .records()
+            .filter(|mp| mp.is_online())
+            .map(|mp| mp.uuid)
+            .collect::<HashSet<_>>();
+
+        let pids = pt.records().map(|rec| rec.pid).collect::<HashSet<_>>();
+        let removed = store
+            .scanned
+            .keys()
+            .filter(|pid| !pids.contains(pid))
+            .cloned()
+            .collect::<Vec<_>>();
+        removed.into_iter().for_each(|pid| store.remove(pid));
+
+        let selected = match self.queries.is_empty() {
+            true => None,
+            false => Some(pt.select(&self.queries)?),
+        };
+        let recs = pt
+            .records()
+            .filter(|rec| selected.as_ref().map_or(true, |x| x.contains(&rec.pid)))
+            .filter(|rec| self.force || !store.is_scanned(rec.pid, rec.file_hash))
+            .filter(|rec| online.contains(&rec.location.mpid))
+            .collect::<Vec<_>>();
+
+        use rayon::prelude::*;
+        let progress = bar(recs.len(), "Detecting faces");
+        let results = recs
+            .into_par_iter()
+            .map(|rec| {
+                let detect = || -> Result<_> {
+                    let img = ImageReader::open(rec.location.filepath()?)?.decode()?;
+                    let img = rec.effective_metadata().orientation.apply(img);
+                    detector.detect(&img)
+                };
+                let res = detect().map(|faces| (rec.pid, rec.file_hash, faces));
+                // left unscanned, so that the next run tries again
+                if let Err(e) = &res {
+                    warn!("Skipped {}: {:#}", rec.location, e);
+                }
+                progress.inc(1);
+                res.ok()
+            })
+            .collect::<Vec<_>>();
+        progress.finish_and_clear();
+        let nskipped = results.iter().filter(|x| x.is_none()).count();
+        let found = results.into_iter().flatten().collect::<Vec<_>>();
+
+        // in order of PID, so that clusters come out the same every time
+        let (nphotos, nfaces) = (found.len(), found.iter().map(|x| x.2.len()).sum::<usize>());
+        for (pid, file_hash, faces) in found {
+            store.put(pid, file_hash, faces);
+        }
+        store.finalize()?;
+        info!(
+            "Found {} faces in {} photos, {} people in total",
+            nfaces,
+            nphotos,
+            store.clusters.len()
+        );
+        if nskipped > 0 {
+            warn!("{} photos could not be read and were skipped", nskipped);
+        }
+        Ok(())
+    }
+}
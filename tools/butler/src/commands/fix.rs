@@ -1,24 +1,82 @@
 use crate::prelude::*;
+use crate::util::time::parse_time;
 use clap::Args;
 
 #[derive(Args)]
 pub(super) struct Fix {
-    dst: PhotoQuery,
-    src: PhotoQuery,
+    #[clap(help = "Photos to fix, e.g. 42 or 'mount:sd & date:2021-05-03'")]
+    target: PhotoFilter,
+    #[clap(long, help = "Copy the EXIF time from another photo")]
+    etime_from: Option<PhotoQuery>,
+    #[clap(long, help = "Override the EXIF time, e.g. 2021-05-03T14:00:00")]
+    etime: Option<String>,
+    #[clap(
+        long,
+        help = "Override the orientation, e.g. 90, or m90 for a mirrored one"
+    )]
+    orientation: Option<PhotoOrientation>,
+    #[clap(long, help = "Drop the overridden EXIF time and orientation")]
+    clear: bool,
 }
 
 impl Fix {
-    pub(super) fn run(self) -> Result<()> {
-        let mut pt = pt_access_mut();
-        pt.initialize(DEFAULT_PHOTOS_DB_PATH)?;
-        let metadata2 = pt.query(self.dst).unwrap().metadata.clone();
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        let mut pt = db.photos()?;
 
-        pt.entry(self.src)
-            .modify()
-            .unwrap()
-            .set_metadata_with(|mdata| mdata.fix_from(&metadata2))
-            .commit();
-        pt.finalize(DEFAULT_PHOTOS_DB_PATH)?;
+        let pids = pt.select(Some(&self.target))?;
+        if pids.is_empty() {
+            bail!("no photo matches the query");
+        }
+        let etime_from = match (&self.etime_from, &self.etime) {
+            (Some(_), Some(_)) => bail!("cannot use --etime-from with --etime"),
+            (Some(src), None) => Some(
+                pt.query(src.clone())
+                    .and_then(PhotoRecord::etime)
+                    .ok_or_else(|| anyhow!("{:?} does not have an EXIF time", src))?,
+            ),
+            _ => None,
+        };
+        let tzs = pt
+            .mounts()
+            .records()
+            .map(|mp| (mp.uuid, mp.utc_offset))
+            .collect::<HashMap<_, _>>();
+
+        let targets = pt
+            .records_of(pids)
+            .map(|rec| (rec.pid, rec.location.mpid))
+            .collect::<Vec<_>>();
+        for (pid, mpid) in targets {
+            // a bare time is in the timezone of the mount the photo is on
+            let etime = match &self.etime {
+                Some(time) => {
+                    let tz = tzs.get(&mpid).cloned().unwrap_or_default();
+                    Some(parse_time(time, &tz.fixed())?)
+                }
+                None => etime_from,
+            };
+            let (clear, orientation) = (self.clear, self.orientation);
+            pt.entry(pid)
+                .modify()
+                .unwrap()
+                .set_overrides_with(|overrides| {
+                    // captions are set with `butler caption`
+                    if clear {
+                        *overrides = PhotoOverrides {
+                            caption: overrides.caption.take(),
+                            ..Default::default()
+                        };
+                    }
+                    if etime.is_some() {
+                        overrides.etime = etime;
+                    }
+                    if orientation.is_some() {
+                        overrides.orientation = orientation;
+                    }
+                })
+                .commit();
+        }
+        pt.finalize()?;
         Ok(())
     }
 }
@@ -1,54 +1,24 @@
+use crate::config::{CropMode, Rendition, RenditionFormat};
 use crate::db;
 use crate::prelude::*;
 use crate::util;
 use atomicwrites::{AllowOverwrite, AtomicFile};
+use image::imageops::FilterType;
 use image::io::Reader as ImageReader;
-use image::jpeg::JpegEncoder;
+use image::ImageOutputFormat;
 
-struct EncodeAsOriginal {
-    subdir: &'static str,
-}
-
-struct EncodeAsThumbnail {
-    subdir: &'static str,
-    height: u32,
-    width: u32,
-}
-
-enum EncoderParameter {
-    Original(EncodeAsOriginal),
-    Minify(EncodeAsThumbnail),
-}
-
-impl EncoderParameter {
-    fn subdir(&self) -> &'static str {
-        use EncoderParameter::*;
-        match self {
-            Original(EncodeAsOriginal { subdir }) => subdir,
-            Minify(EncodeAsThumbnail { subdir, .. }) => subdir,
-        }
-    }
-}
-
-const ENCODER_PARAMETERS: [EncoderParameter; 3] = [
-    EncoderParameter::Minify(EncodeAsThumbnail {
-        subdir: "s",
-        height: 100,
-        width: 100,
-    }),
-    EncoderParameter::Minify(EncodeAsThumbnail {
-        subdir: "m",
-        height: 800,
-        width: 800,
-    }),
-    EncoderParameter::Original(EncodeAsOriginal { subdir: "source" }),
-];
+// below which differences are left alone by sharpening
+const SHARPEN_THRESHOLD: i32 = 2;
 
 #[derive(Debug)]
-pub struct PhotoGenerator {
+pub struct PhotoGenerator<'a> {
     pid: u32,
     dst_dir: PathBuf,
-    quality: u8,
+    renditions: &'a [Rendition],
+    // as last generated, whose files may still be around
+    old_renditions: &'a [Rendition],
+    // names of renditions whose settings changed since last generated
+    changed: &'a HashSet<String>,
     force: bool,
     source_path: PathBuf,
     metadata: db::PhotoMetadata,
@@ -64,11 +34,17 @@ pub struct CompressedMeta {
     w: u8,
 }
 
-pub fn write_bins<P: AsRef<Path>>(mut metas: Vec<CompressedMeta>, path: P) -> Result<()> {
+impl CompressedMeta {
+    pub fn pid(&self) -> PID {
+        self.pid
+    }
+}
+
+pub fn write_bins<P: AsRef<Path>>(metas: &mut [CompressedMeta], path: P) -> Result<()> {
     metas.sort_by_cached_key(|x| x.timestamp);
     use byteorder::{BigEndian, WriteBytesExt};
     let mut writer = BufWriter::new(File::create(path)?);
-    for meta in metas {
+    for meta in metas.iter() {
         writer.write_uint::<BigEndian>(meta.pid.into(), 3)?;
         writer.write_u32::<BigEndian>(meta.timestamp)?;
         writer.write_u8(meta.h)?;
@@ -77,23 +53,73 @@ pub fn write_bins<P: AsRef<Path>>(mut metas: Vec<CompressedMeta>, path: P) -> Re
     Ok(())
 }
 
-impl PhotoGenerator {
+// For each album by name: the length of its name (u8), the name in UTF-8,
+// the number of photos (u24) and their PIDs (u24) in the order of images.bin.
+pub fn write_albums<P: AsRef<Path>>(albums: &BTreeMap<&str, Vec<PID>>, path: P) -> Result<()> {
+    use byteorder::{BigEndian, WriteBytesExt};
+    let mut writer = BufWriter::new(File::create(path)?);
+    for (name, pids) in albums {
+        writer.write_u8(name.len() as u8)?;
+        writer.write_all(name.as_bytes())?;
+        writer.write_uint::<BigEndian>(pids.len() as u64, 3)?;
+        for pid in pids {
+            writer.write_uint::<BigEndian>((*pid).into(), 3)?;
+        }
+    }
+    Ok(())
+}
+
+// Captions by PID in ascending order, each as the PID (u24), the length
+// of the text (u16) and the text in UTF-8. See notes/captions.bin.md.
+pub fn write_captions<P: AsRef<Path>>(captions: &BTreeMap<PID, &str>, path: P) -> Result<()> {
+    use byteorder::{BigEndian, WriteBytesExt};
+    let mut writer = BufWriter::new(File::create(path)?);
+    for (pid, text) in captions {
+        let len = u16::try_from(text.len())
+            .map_err(|_| anyhow!("caption of {} is longer than {} bytes", pid, u16::MAX))?;
+        writer.write_uint::<BigEndian>((*pid).into(), 3)?;
+        writer.write_u16::<BigEndian>(len)?;
+        writer.write_all(text.as_bytes())?;
+    }
+    Ok(())
+}
+
+// GPS positions by PID in ascending order, each as the PID (u24), the
+// latitude and the longitude (i32, in 1e-7 degrees). See notes/locations.bin.md.
+pub fn write_locations<P: AsRef<Path>>(
+    locations: &BTreeMap<PID, GeoPosition>,
+    path: P,
+) -> Result<()> {
+    use byteorder::{BigEndian, WriteBytesExt};
+    let mut writer = BufWriter::new(File::create(path)?);
+    for (pid, gps) in locations {
+        writer.write_uint::<BigEndian>((*pid).into(), 3)?;
+        writer.write_i32::<BigEndian>(gps.latitude)?;
+        writer.write_i32::<BigEndian>(gps.longitude)?;
+    }
+    Ok(())
+}
+
+impl<'a> PhotoGenerator<'a> {
     pub fn new<P: AsRef<Path>>(
         entry: &db::PhotoRecord,
         dst_dir: P,
         force: bool,
-        quality: u8,
+        renditions: &'a [Rendition],
+        old_renditions: &'a [Rendition],
+        changed: &'a HashSet<String>,
     ) -> Result<Self> {
         Ok(Self {
             force,
-            quality,
+            renditions,
+            old_renditions,
+            changed,
             dst_dir: dst_dir.as_ref().into(),
             pid: entry.pid,
-            source_path: entry.location.filepath().into(),
-            metadata: entry.metadata.clone(),
+            source_path: entry.location.filepath()?.into(),
+            metadata: entry.effective_metadata(),
             exif_time: entry
-                .metadata
-                .etime
+                .etime()
                 .ok_or_else(|| anyhow!("{} does not have an EXIF time", entry.pid))?,
             commit_time: entry
                 .commit_time
@@ -101,15 +127,10 @@ impl PhotoGenerator {
             image: None,
         })
     }
-    fn get_compressed_meta(self) -> Result<CompressedMeta> {
-        use db::PhotoOrientation::*;
-        let (h, w) = match (
-            self.metadata.orientation,
-            self.metadata.height,
-            self.metadata.width,
-        ) {
-            (D0 | D180, h, w) => (h, w),
-            (_, h, w) => (w, h),
+    pub fn into_meta(self) -> Result<CompressedMeta> {
+        let (h, w) = match (self.metadata.height, self.metadata.width) {
+            (h, w) if self.metadata.orientation.swaps_dims() => (w, h),
+            (h, w) => (h, w),
         };
         if h == 0 || w == 0 {
             bail!("{} has invalid dimensions", self.pid);
@@ -123,19 +144,13 @@ impl PhotoGenerator {
         })
     }
     pub fn generate(mut self) -> Result<CompressedMeta> {
-        for param in &ENCODER_PARAMETERS {
-            self.generate_for(param)?;
+        for rendition in self.renditions {
+            self.generate_for(rendition)?;
         }
-        Ok(self.get_compressed_meta()?)
+        Ok(self.into_meta()?)
     }
     fn rotate_image(&self, img: image::DynamicImage) -> image::DynamicImage {
-        use db::PhotoOrientation::*;
-        match self.metadata.orientation {
-            D270 => img.rotate270(),
-            D180 => img.rotate180(),
-            D90 => img.rotate90(),
-            D0 => img,
-        }
+        self.metadata.orientation.apply(img)
     }
     fn image_ref(&mut self) -> Result<&image::DynamicImage> {
         if self.image.is_none() {
@@ -145,11 +160,29 @@ impl PhotoGenerator {
         }
         Ok(self.image.as_ref().unwrap())
     }
-    fn generate_for(&mut self, param: &EncoderParameter) -> Result<()> {
-        let dst_dir = self.dst_dir.join(param.subdir());
-        fs::create_dir_all(&dst_dir)?;
-        let dst_file = dst_dir.join(format!("{}.jpg", self.pid));
-        if !self.force && dst_file.exists() {
+    fn dst_file(&self, rendition: &Rendition) -> PathBuf {
+        self.dst_dir
+            .join(&rendition.name)
+            .join(format!("{}.{}", self.pid, rendition.extension()))
+    }
+    // the file of the rendition as last generated, if its format differs
+    fn old_file(&self, rendition: &Rendition) -> Option<PathBuf> {
+        self.old_renditions
+            .iter()
+            .find(|x| x.name == rendition.name && x.extension() != rendition.extension())
+            .map(|old| self.dst_file(old))
+    }
+    // in the current format or, until regenerated, in the old one
+    pub fn is_generated(&self) -> bool {
+        self.renditions.iter().all(|rendition| {
+            self.dst_file(rendition).exists()
+                || self.old_file(rendition).map_or(false, |x| x.exists())
+        })
+    }
+    fn generate_for(&mut self, rendition: &Rendition) -> Result<()> {
+        let dst_file = self.dst_file(rendition);
+        fs::create_dir_all(dst_file.parent().unwrap())?;
+        if !self.force && !self.changed.contains(&rendition.name) && dst_file.exists() {
             let mtime: DateTime<Utc> = dst_file.metadata()?.modified()?.into();
             if mtime >= self.commit_time {
                 println!("Already generated: {}", dst_file.display());
@@ -158,24 +191,46 @@ impl PhotoGenerator {
         }
 
         println!("Generating {}...", dst_file.display());
-        use EncoderParameter::*;
-        match param {
-            Original(v) => self.original(dst_file, v),
-            Minify(v) => self.minify(dst_file, v),
+        match rendition.format {
+            RenditionFormat::Link => self.link(dst_file)?,
+            RenditionFormat::Jpeg => self.encode(
+                dst_file,
+                rendition,
+                ImageOutputFormat::Jpeg(rendition.quality),
+            )?,
+            RenditionFormat::Png => self.encode(dst_file, rendition, ImageOutputFormat::Png)?,
+        }
+        // replaced only now, so that a failure leaves the old one in use
+        if let Some(old_file) = self.old_file(rendition) {
+            if old_file.exists() {
+                fs::remove_file(old_file)?;
+            }
         }
+        Ok(())
     }
-    fn minify(&mut self, dst: PathBuf, param: &EncodeAsThumbnail) -> Result<()> {
-        let thumbnail = self.image_ref()?.thumbnail(param.width, param.height);
+    fn encode(
+        &mut self,
+        dst: PathBuf,
+        rendition: &Rendition,
+        format: ImageOutputFormat,
+    ) -> Result<()> {
+        let img = self.image_ref()?;
+        let img = match (rendition.max_size, rendition.crop) {
+            (None, _) => img.clone(),
+            (Some((w, h)), CropMode::Fit) => img.thumbnail(w, h),
+            (Some((w, h)), CropMode::Fill) => img.resize_to_fill(w, h, FilterType::Triangle),
+        };
+        let img = match rendition.sharpen {
+            Some(sigma) => img.unsharpen(sigma, SHARPEN_THRESHOLD),
+            None => img,
+        };
 
         AtomicFile::new(dst, AllowOverwrite)
-            .write(|file| {
-                let mut encoder = JpegEncoder::new_with_quality(file, self.quality);
-                encoder.encode_image(&thumbnail)
-            })
+            .write(|file| img.write_to(file, format))
             .map_err(Error::new)
     }
 
-    fn original(&mut self, dst: PathBuf, _param: &EncodeAsOriginal) -> Result<()> {
+    fn link(&mut self, dst: PathBuf) -> Result<()> {
         let _ = std::fs::remove_file(&dst);
         std::os::unix::fs::symlink(&self.source_path, &dst).or_else(|err| match err.kind() {
             std::io::ErrorKind::AlreadyExists => Ok(()),
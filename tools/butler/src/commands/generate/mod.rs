@@ -1,3 +1,4 @@
+use crate::config::{config, Rendition};
 use crate::prelude::*;
 use clap::Args;
 
@@ -8,27 +9,163 @@ use generator::PhotoGenerator;
 pub(super) struct Generate {
     #[clap(default_value_t = String::from("./assets/_generated"))]
     dest: String,
-    #[clap(default_value_t = 85)]
-    quality: u8,
     #[clap(short, long)]
     force: bool,
+    #[clap(
+        long,
+        help = "Only render photos matching these queries, the others keep their renditions"
+    )]
+    only: Vec<PhotoFilter>,
+    #[clap(
+        long,
+        default_value_t = 0,
+        parse(try_from_str = parse_rating),
+        help = "Leave out selected photos rated below this"
+    )]
+    min_rating: u8,
+}
+
+// renditions generated last time, to tell what changed in config.json
+const MANIFEST_FILENAME: &str = "renditions.json";
+
+fn read_manifest(dest: &Path) -> Result<Vec<Rendition>> {
+    let path = dest.join(MANIFEST_FILENAME);
+    if !path.exists() {
+        // generated before renditions were configurable
+        return Ok(Rendition::defaults());
+    }
+    serde_json::from_slice(&fs::read(&path)?)
+        .map_err(|e| anyhow!("Error reading {}: {}", path.display(), e))
+}
+
+// removes the renditions no longer configured, and records the current ones
+fn update_manifest(dest: &Path, old: &[Rendition], new: &[Rendition]) -> Result<()> {
+    for rendition in old {
+        if new.iter().any(|x| x.name == rendition.name) {
+            continue;
+        }
+        let dir = dest.join(&rendition.name);
+        if dir.exists() {
+            info!("Removing obsolete rendition {}", dir.display());
+            fs::remove_dir_all(dir)?;
+        }
+    }
+    fs::write(
+        dest.join(MANIFEST_FILENAME),
+        serde_json::to_vec_pretty(new)?,
+    )?;
+    Ok(())
 }
 
 impl Generate {
-    pub(super) fn run(self) -> Result<()> {
+    // whether a photo is written out, ignoring --only
+    pub(super) fn wants(&self, rec: &PhotoRecord) -> bool {
+        rec.selected && rec.rating >= self.min_rating
+    }
+    pub(super) fn generate_from(&self, pt: &PhotoTableAccess<'_>) -> Result<()> {
         use rayon::prelude::*;
-        let pt = pt_access_mut();
-        pt.initialize(DEFAULT_PHOTOS_DB_PATH)?;
-        let dest = PathBuf::from(self.dest);
-        let metas = pt
+        let dest = PathBuf::from(&self.dest);
+        fs::create_dir_all(&dest)?;
+        let renditions = &config().renditions;
+        let old_renditions = read_manifest(&dest)?;
+        // regenerated as a whole, as if forced
+        let changed = renditions
+            .iter()
+            .filter(|x| old_renditions.iter().any(|y| y.name == x.name && y != *x))
+            .map(|x| x.name.clone())
+            .collect::<HashSet<_>>();
+        if !changed.is_empty() {
+            info!("Renditions changed, regenerating: {:?}", changed);
+        }
+        let only = match self.only.is_empty() {
+            true => None,
+            false => Some(pt.select(&self.only)?),
+        };
+        let online = pt
+            .mounts()
             .records()
-            .filter(|entry| entry.selected)
-            .map(|entry| generator::PhotoGenerator::new(entry, &dest, self.force, self.quality))
-            .collect::<Result<Vec<_>>>()?
+            .filter(|mp| mp.is_online())
+            .map(|mp| mp.uuid)
+            .collect::<HashSet<_>>();
+
+        let mut jobs = vec![];
+        let (mut nstale, mut nskipped) = (0, 0);
+        for entry in pt.records().filter(|entry| self.wants(entry)) {
+            let gen = PhotoGenerator::new(
+                entry,
+                &dest,
+                self.force,
+                renditions,
+                &old_renditions,
+                &changed,
+            )?;
+            // sources on offline mount points cannot be read, reuse what was generated before
+            if !online.contains(&entry.location.mpid) {
+                match gen.is_generated() {
+                    true => nstale += 1,
+                    false => {
+                        nskipped += 1;
+                        continue;
+                    }
+                }
+                jobs.push((false, gen));
+                continue;
+            }
+            let render = only
+                .as_ref()
+                .map(|x| x.contains(&entry.pid))
+                .unwrap_or(true);
+            jobs.push((render, gen));
+        }
+        if nstale + nskipped > 0 {
+            warn!(
+                "{} photos are on offline mount points, {} of them are skipped as never generated",
+                nstale + nskipped,
+                nskipped
+            );
+        }
+
+        let mut metas = jobs
             .into_par_iter()
-            .map(PhotoGenerator::generate)
+            .map(|(render, gen)| match render {
+                true => gen.generate(),
+                false => gen.into_meta(),
+            })
             .collect::<Result<Vec<_>>>()?;
-        generator::write_bins(metas, dest.join("images.bin"))?;
+        generator::write_bins(&mut metas, dest.join("images.bin"))?;
+
+        let mut albums = BTreeMap::<_, Vec<_>>::new();
+        let mut captions = BTreeMap::new();
+        let mut locations = BTreeMap::new();
+        for rec in pt.records_of(metas.iter().map(|meta| meta.pid())) {
+            for name in rec.albums.iter() {
+                albums.entry(name.as_str()).or_default().push(rec.pid);
+            }
+            if let Some(caption) = &rec.overrides.caption {
+                captions.insert(rec.pid, caption.as_str());
+            }
+            if let Some(gps) = rec.metadata.gps {
+                locations.insert(rec.pid, gps);
+            }
+        }
+        generator::write_albums(&albums, dest.join("albums.bin"))?;
+        generator::write_captions(&captions, dest.join("captions.bin"))?;
+        generator::write_locations(&locations, dest.join("locations.bin"))?;
+        // photos left out this time keep the old settings, to be redone later
+        let complete = only.is_none() && nstale + nskipped == 0;
+        let recorded = renditions
+            .iter()
+            .map(|x| match !complete && changed.contains(&x.name) {
+                true => old_renditions.iter().find(|y| y.name == x.name).unwrap(),
+                false => x,
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        update_manifest(&dest, &old_renditions, &recorded)?;
         Ok(())
     }
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        let pt = db.photos()?;
+        self.generate_from(&pt.access())
+    }
 }
@@ -12,7 +12,8 @@ where
             rec.pid,
             rec.location.filename.extension().unwrap().to_string_lossy()
         ));
-        std::os::unix::fs::symlink(rec.location.filepath(), dst.as_path())?;
+        let src = unwrap_some_or!(rec.location.try_filepath(), { continue });
+        std::os::unix::fs::symlink(src, dst.as_path())?;
     }
     Ok(())
 }
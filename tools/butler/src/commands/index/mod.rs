@@ -1,3 +1,4 @@
+use super::util::display::print_quarantine;
 use crate::prelude::*;
 use clap::Args;
 
@@ -5,18 +6,45 @@ mod link;
 use link::symlink_all_photos_to;
 
 mod scanner;
+pub(super) use scanner::{ScanOptions, Scanner};
 
 #[derive(Args)]
-pub(super) struct Index {}
+pub(super) struct Index {
+    #[clap(short, long, help = "Number of threads to scan with")]
+    jobs: Option<usize>,
+    #[clap(long, help = "Re-read EXIF even for files that look unchanged")]
+    full: bool,
+    #[clap(long, help = "Abort on the first unreadable file")]
+    strict: bool,
+}
 
 impl Index {
-    pub(super) fn run(self) -> Result<()> {
-        let mut pt = pt_access_mut();
-        pt.initialize(DEFAULT_PHOTOS_DB_PATH)?;
-        scanner::Scanner::new(&mut pt, &mpt_access()).run()?;
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        if let Some(jobs) = self.jobs {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build_global()?;
+        }
+        let mut pt = db.photos()?;
+        let opts = ScanOptions {
+            full: self.full,
+            strict: self.strict,
+        };
+        let mut quarantine = Scanner::new(&mut pt, opts).run()?;
+        let nstill = quarantine.carry_over(Quarantine::load_from_path(DEFAULT_QUARANTINE_PATH)?);
         symlink_all_photos_to(&pt, ".butler/links/")?;
         pt.summary();
-        pt.finalize(DEFAULT_PHOTOS_DB_PATH)?;
+        pt.finalize()?;
+        db.mounts_mut().clear_rescan();
+        quarantine.finalize(DEFAULT_QUARANTINE_PATH)?;
+        if !quarantine.files.is_empty() {
+            println!(
+                "{} files could not be read, {} of them already in an earlier run:",
+                quarantine.files.len(),
+                nstill
+            );
+            print_quarantine(&quarantine);
+        }
         Ok(())
     }
 }
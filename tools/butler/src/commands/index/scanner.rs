@@ -3,7 +3,7 @@ use std::fs::DirEntry;
 use crate::prelude::*;
 use crate::util;
 use crate::util::functional::*;
-
+use rayon::prelude::*;
 
 fn is_file(file: &std::io::Result<DirEntry>) -> bool {
     file.as_ref()
@@ -21,13 +21,13 @@ fn is_supported_image(file: &std::io::Result<DirEntry>) -> bool {
         .unwrap_or(false)
 }
 
-struct ClassifiedResult<'a> {
+struct ClassifiedResult {
     existing_pids: HashSet<PID>,
     new_lphotos: Vec<LocalPhoto>,
-    photo_record_diffs: Vec<PhotoRecordDiff<'a>>,
+    photo_record_diffs: Vec<PhotoRecordDiff>,
 }
 
-impl<'a> ClassifiedResult<'a> {
+impl ClassifiedResult {
     fn new() -> Self {
         Self {
             existing_pids: HashSet::new(),
@@ -35,7 +35,7 @@ impl<'a> ClassifiedResult<'a> {
             photo_record_diffs: vec![],
         }
     }
-    fn into_tuple(self) -> (HashSet<PID>, Vec<LocalPhoto>, Vec<PhotoRecordDiff<'a>>) {
+    fn into_tuple(self) -> (HashSet<PID>, Vec<LocalPhoto>, Vec<PhotoRecordDiff>) {
         (
             self.existing_pids,
             self.new_lphotos,
@@ -44,17 +44,127 @@ impl<'a> ClassifiedResult<'a> {
     }
 }
 
+impl Accumulable for ClassifiedResult {
+    fn init() -> Self {
+        Self::new()
+    }
+    fn accum(x: Self, y: Self) -> Self {
+        let (existing_pids, new_lphotos, photo_record_diffs) =
+            Accumulable::accum(x.into_tuple(), y.into_tuple());
+        Self {
+            existing_pids,
+            new_lphotos,
+            photo_record_diffs,
+        }
+    }
+}
+
+pub struct ScanOptions {
+    pub full: bool,
+    pub strict: bool,
+}
+
+// a file to scan, along with its record from the last run if any
+struct ScanTask<'a> {
+    location: Arc<FileLocation>,
+    default_tz: FixedOffset,
+    // EXIF read again even if the file looks unchanged
+    full: bool,
+    rec: Option<&'a PhotoRecord>,
+}
+
+impl<'a> ScanTask<'a> {
+    // classifies the file, and turns a failure into a quarantined entry
+    // unless `strict`, with the old record kept intact
+    fn classify_or_quarantine(
+        self,
+        opts: &ScanOptions,
+    ) -> Result<Either<ClassifiedResult, QuarantinedFile>> {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let location = self.location.clone();
+        let pid = self.rec.map(|rec| rec.pid);
+        let full = opts.full || self.full;
+        let error = match catch_unwind(AssertUnwindSafe(|| self.classify(full))) {
+            Ok(Ok(res)) => return Ok(Ok(res)),
+            Ok(Err(e)) if opts.strict => return Err(e),
+            Ok(Err(e)) => format!("{:#}", e),
+            Err(_) if opts.strict => {
+                bail!("panicked while scanning {}", location)
+            }
+            Err(_) => "panicked while scanning".to_string(),
+        };
+        warn!("Quarantined {}: {}", location, error);
+        Ok(Err(QuarantinedFile {
+            location,
+            pid,
+            error,
+            time: Utc::now(),
+        }))
+    }
+    // the fast path, which only stats the file
+    fn is_unchanged(&self, rec: &PhotoRecord) -> Result<bool> {
+        if rec.status == CommittedButMissing || rec.fingerprint.is_none() {
+            return Ok(false);
+        }
+        rec.metadata
+            .stat_matches(&self.location.filepath()?.metadata()?)
+    }
+    fn classify(self, full: bool) -> Result<ClassifiedResult> {
+        let mut res = ClassifiedResult::new();
+        if let Some(rec) = self.rec {
+            if !full && self.is_unchanged(rec)? {
+                res.existing_pids.insert(rec.pid);
+                return Ok(res);
+            }
+        }
+
+        let mut lphoto = LocalPhoto::new(self.location, self.default_tz)?;
+        let rec = unwrap_some_or!(self.rec, {
+            lphoto.prefetch()?;
+            lphoto.fill_fingerprint()?;
+            lphoto.fill_file_hash()?;
+            res.new_lphotos.push(lphoto);
+            return Ok(res);
+        });
+
+        let need_hash_check = lphoto.metadata != rec.metadata || rec.status == CommittedButMissing;
+        let need_fingerprint = need_hash_check || rec.fingerprint.is_none();
+        if !need_fingerprint {
+            res.existing_pids.insert(rec.pid);
+            return Ok(res);
+        }
+
+        lphoto.prefetch()?;
+        lphoto.fill_fingerprint()?;
+        let mut diff = PhotoRecordDiff::new(rec.pid);
+        diff.fingerprint.set(lphoto.fingerprint);
+        if need_hash_check {
+            lphoto.fill_file_hash()?;
+            diff.metadata.set(lphoto.metadata.clone());
+            diff.file_hash.set(lphoto.file_hash.unwrap());
+        }
+        res.photo_record_diffs.push(diff);
+        Ok(res)
+    }
+}
+
 struct MountPointScanner<'b, 'a: 'b> {
     loc: DirectoryLocation,
-    access: &'b PhotoTableAccessMut<'a>,
+    default_tz: FixedOffset,
+    // the timezone changed since the times were read
+    rescan: bool,
+    access: &'b PhotoTableAccess<'a>,
 }
 
 impl<'b, 'a: 'b> MountPointScanner<'b, 'a> {
-    fn new(mp: &MountPointRecord, access: &'b PhotoTableAccessMut<'a>) -> Result<Self> {
-        Ok(Self {
+    fn new(mp: &MountPointRecord, access: &'b PhotoTableAccess<'a>) -> Self {
+        Self {
             loc: mp.into(),
+            default_tz: mp.utc_offset.fixed(),
+            rescan: mp.rescan,
             access,
-        })
+        }
     }
     fn sorted_files(&self) -> Result<impl IntoIterator<Item = DirEntry>> {
         use std::os::unix::fs::DirEntryExt;
@@ -68,71 +178,66 @@ impl<'b, 'a: 'b> MountPointScanner<'b, 'a> {
         files.sort_by_cached_key(DirEntry::ino);
         Ok(files)
     }
-    fn to_lphoto(&self, file: DirEntry) -> Result<LocalPhoto> {
-        let loc = self.loc.with_filename(file.file_name());
-        let lphoto = LocalPhoto::new(loc)?;
-        Ok(lphoto)
-    }
-    fn classify<In>(&self, lphotos: In) -> Result<ClassifiedResult<'a>>
-    where
-        In: IntoIterator<Item = Result<LocalPhoto>>,
-    {
-        let mut res = ClassifiedResult::new();
-        for lphoto in lphotos.into_iter() {
-            let mut lphoto = lphoto?;
-            let rec = unwrap_some_or!(self.access.query::<Arc<_>, _>(&lphoto.location), {
-                lphoto.prefetch()?;
-                lphoto.fill_file_hash()?;
-                res.new_lphotos.push(lphoto);
-                continue;
-            });
-
-            let need_hash_check =
-                lphoto.metadata != rec.metadata || rec.status == CommittedButMissing;
-            if !need_hash_check {
-                res.existing_pids.insert(rec.pid);
-                continue;
-            }
-
-            lphoto.prefetch()?;
-            lphoto.fill_file_hash()?;
-            let mut diff = PhotoRecordDiff::new(rec);
-            diff.metadata.set(lphoto.metadata.clone());
-            diff.file_hash.set(lphoto.file_hash.unwrap());
-            res.photo_record_diffs.push(diff);
+    fn to_task<P: AsRef<Path>>(&self, filename: P) -> ScanTask<'a> {
+        let location = Arc::new(self.loc.with_filename(filename));
+        let rec = self.access.query::<Arc<_>, _>(&location);
+        ScanTask {
+            location,
+            default_tz: self.default_tz,
+            full: self.rescan,
+            rec,
         }
-        Ok(res)
     }
-    fn run(self) -> Result<ClassifiedResult<'a>> {
-        let lphotos = self
+    fn tasks(self) -> Result<Vec<ScanTask<'a>>> {
+        Ok(self
             .sorted_files()?
             .into_iter()
-            .map(|file| self.to_lphoto(file));
-        self.classify(lphotos)
+            .map(|file| self.to_task(file.file_name()))
+            .collect())
     }
 }
 
 pub struct Scanner<'b, 'a: 'b> {
     pt: &'b mut PhotoTableAccessMut<'a>,
-    mpt: &'b TableAccess<'a, MountPointTable>,
+    opts: ScanOptions,
 }
 
 impl<'b, 'a: 'b> Scanner<'b, 'a> {
-    pub fn new(
-        pt: &'b mut PhotoTableAccessMut<'a>,
-        mpt: &'b TableAccess<'a, MountPointTable>,
-    ) -> Self {
-        Self { pt, mpt }
-    }
-    pub fn run(self) -> Result<()> {
-        let (mut pids, new_lphotos, diffs) = self
-            .mpt
-            .records()
-            .map(|mp| MountPointScanner::new(mp, &self.pt))
-            .map(|s| -> Result<_> { Ok(s?.run()?.into_tuple()) })
-            .collect::<Result<Vec<_>>>()?
+    pub fn new(pt: &'b mut PhotoTableAccessMut<'a>, opts: ScanOptions) -> Self {
+        Self { pt, opts }
+    }
+    fn classify(&self, tasks: Vec<ScanTask<'_>>) -> Result<(ClassifiedResult, Quarantine)> {
+        let opts = &self.opts;
+        let progress = util::progress::bar(tasks.len(), "Scanning");
+        let results = tasks
+            .into_par_iter()
+            .map(|task| {
+                let res = task.classify_or_quarantine(opts);
+                progress.inc(1);
+                res
+            })
+            .collect::<Result<Vec<_>>>()?;
+        progress.finish_and_clear();
+
+        let mut quarantine = Quarantine::default();
+        let mut res = results
             .into_iter()
+            .filter_map(|res| match res {
+                Ok(res) => Some(res),
+                Err(file) => {
+                    quarantine.files.push(file);
+                    None
+                }
+            })
             .fold_into();
+        // records of quarantined files are kept as they are
+        res.existing_pids
+            .extend(quarantine.files.iter().filter_map(|file| file.pid));
+        Ok((res, quarantine))
+    }
+    // writes classified results into the table, returns PIDs of the scanned files
+    fn apply(&mut self, res: ClassifiedResult) -> HashSet<PID> {
+        let (mut pids, new_lphotos, diffs) = res.into_tuple();
 
         new_lphotos.into_iter().for_each(|file| {
             let pid = self.pt.insert_lphoto(file);
@@ -145,6 +250,36 @@ impl<'b, 'a: 'b> Scanner<'b, 'a> {
             pids.insert(pid);
         });
 
+        pids
+    }
+    pub fn run(mut self) -> Result<Quarantine> {
+        // files of all mount points are pooled, so that slow disks
+        // do not keep the other ones waiting
+        let mounts = self.pt.mounts();
+        let online = mounts
+            .records()
+            .filter(|mp| mp.is_online())
+            .collect::<Vec<_>>();
+        let access = self.pt.access();
+        let tasks = online
+            .iter()
+            .map(|mp| MountPointScanner::new(mp, &access).tasks())
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let (res, quarantine) = self.classify(tasks)?;
+        let mut pids = self.apply(res);
+        // photos on offline mount points are left as they are
+        let online = online.iter().map(|mp| mp.uuid).collect::<HashSet<_>>();
+        pids.extend(
+            self.pt
+                .records()
+                .filter(|rec| !online.contains(&rec.location.mpid))
+                .map(|rec| rec.pid),
+        );
+
         self.pt.retain(|pid, rec| {
             if pids.contains(pid) {
                 return true;
@@ -153,6 +288,54 @@ impl<'b, 'a: 'b> Scanner<'b, 'a> {
             *rec.selected()
         });
 
-        Ok(())
+        Ok(quarantine)
+    }
+    // Updates records of the given files only, as `run` would do. Paths
+    // outside of mount points or of unsupported types are ignored.
+    pub fn update_files<I>(mut self, paths: I) -> Result<Quarantine>
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        let mounts = self.pt.mounts();
+        let access = self.pt.access();
+        let mut tasks = vec![];
+        let mut gone = vec![];
+        for path in paths {
+            if !util::fs::is_supported_image(&path) {
+                continue;
+            }
+            let (dir, filename) = unwrap_some_or!(path.parent().zip(path.file_name()), {
+                continue;
+            });
+            let mp = mounts
+                .records()
+                .find(|mp| &**mp.path == dir && mp.is_online());
+            let mp = unwrap_some_or!(mp, {
+                continue;
+            });
+            let scanner = MountPointScanner::new(mp, &access);
+            if path.is_file() {
+                tasks.push(scanner.to_task(filename));
+            } else {
+                let location = Arc::new(scanner.loc.with_filename(filename));
+                gone.extend(access.query::<Arc<_>, _>(&location).map(|rec| rec.pid));
+            }
+        }
+
+        let (res, quarantine) = self.classify(tasks)?;
+        self.apply(res);
+
+        for pid in gone {
+            let selected = self.pt.query(pid).map(|rec| rec.selected).unwrap_or(false);
+            if selected {
+                let mut rec = self.pt.entry(pid).modify().unwrap();
+                rec.mark_missing();
+                rec.commit();
+            } else {
+                self.pt.entry(pid).remove();
+            }
+        }
+
+        Ok(quarantine)
     }
 }
@@ -1,22 +1,66 @@
-use super::util::display::print_photos;
+use super::util::display::{print_bursts, print_days, print_photos, print_photos_long};
 use crate::prelude::*;
 use clap::Args;
 
 #[derive(Args)]
 pub(super) struct List {
-    mpid: Option<Uuid>,
+    #[clap(help = "Queries like 'mount:sd & status:missing', all photos if omitted")]
+    queries: Vec<PhotoFilter>,
+    #[clap(short, long, help = "Group burst shots and suggest the best frame")]
+    bursts: bool,
+    #[clap(
+        long,
+        default_value_t = 2,
+        help = "Max seconds between frames of a burst"
+    )]
+    burst_gap: i64,
+    #[clap(
+        long,
+        default_value_t = 10,
+        help = "Max perceptual hash distance in a burst"
+    )]
+    burst_distance: u32,
+    #[clap(
+        short,
+        long,
+        conflicts_with = "bursts",
+        help = "Count photos per day they were taken"
+    )]
+    days: bool,
+    #[clap(short, long, help = "Show time, camera, exposure and GPS too")]
+    long: bool,
 }
 
 impl List {
-    pub(super) fn run(self) -> Result<()> {
-        let pt = pt_access_mut();
-        pt.initialize(DEFAULT_PHOTOS_DB_PATH)?;
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        let pt = db.photos()?;
 
-        let mpid = self.mpid.as_ref();
-        print_photos(
-            pt.records()
-                .filter(|rec| mpid.map(|mpid| mpid == &rec.location.mpid).unwrap_or(true)),
-        );
+        let pids = match self.queries.is_empty() {
+            true => None,
+            false => Some(pt.select(&self.queries)?),
+        };
+        let matches =
+            |rec: &PhotoRecord| pids.as_ref().map(|x| x.contains(&rec.pid)).unwrap_or(true);
+        if self.bursts {
+            let bursts = pt
+                .bursts(self.burst_gap, self.burst_distance)
+                .into_iter()
+                .filter(|burst| burst.members.iter().any(|rec| matches(rec)));
+            print_bursts(bursts);
+            return Ok(());
+        }
+        if self.days {
+            print_days(pt.by_day().into_iter().filter_map(|(date, mut recs)| {
+                recs.retain(|rec| matches(rec));
+                (!recs.is_empty()).then(|| (date, recs))
+            }));
+            return Ok(());
+        }
+        let recs = pt.records().filter(|rec| matches(rec));
+        match self.long {
+            true => print_photos_long(recs),
+            false => print_photos(recs),
+        }
         Ok(())
     }
 }
@@ -0,0 +1,27 @@
+use super::util::display::{print_changes, print_operations};
+use crate::prelude::*;
+use clap::Args;
+
+#[derive(Args)]
+pub(super) struct Log {
+    #[clap(help = "Show the changes of this operation")]
+    id: Option<u32>,
+    #[clap(short = 'n', long, default_value_t = 20, help = "Operations to show")]
+    count: usize,
+}
+
+impl Log {
+    pub(super) fn run(self, _db: &mut Database) -> Result<()> {
+        let log = OperationLog::load()?;
+        match self.id {
+            Some(id) => {
+                let op = unwrap_some_or!(log.operations.iter().find(|op| op.id == id), {
+                    bail!("no operation {} in the log", id)
+                });
+                print_changes(op);
+            }
+            None => print_operations(log.operations.iter().rev().take(self.count)),
+        }
+        Ok(())
+    }
+}
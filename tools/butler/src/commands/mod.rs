@@ -1,12 +1,24 @@
+mod album;
+mod caption;
 mod commit;
+mod db;
+mod faces;
 mod fix;
 mod generate;
 mod index;
 mod list;
+mod log;
 mod mount;
+mod person;
+mod remount;
+mod tag;
+mod timeshift;
 mod umount;
+mod undo;
 mod util;
+mod watch;
 
+use crate::db::Database;
 use anyhow::Result;
 use clap::{CommandFactory, ErrorKind, Parser, Subcommand};
 use paste::paste;
@@ -24,11 +36,11 @@ struct Cli {
     commands: Commands,
 }
 
-pub fn handle_cli() -> Result<()> {
+pub fn handle_cli(db: &mut Database) -> Result<()> {
     let cli = Cli::parse();
     init_logger(&cli);
     init_yansi();
-    dispatch_subcommands(cli.commands)
+    dispatch_subcommands(cli.commands, db)
 }
 
 pub fn init_yansi() {
@@ -98,13 +110,16 @@ macro_rules! make {
             $( $name($name), )*
         }
 
-        fn dispatch_subcommands(subcommands: Commands) -> Result<()> {
+        fn dispatch_subcommands(subcommands: Commands, db: &mut Database) -> Result<()> {
             match subcommands {
-                $( Commands::$name(x) => x.run()?, )*
+                $( Commands::$name(x) => x.run(db)?, )*
             };
             Ok(())
         }
     };
 }
 
-make!(Mount, Umount, List, Index, Fix, Commit, Generate);
+make!(
+    Mount, Umount, Remount, List, Index, Watch, Fix, Timeshift, Commit, Generate, Db, Log, Undo,
+    Tag, Album, Caption, Faces, Person
+);
@@ -1,4 +1,5 @@
-use crate::{prelude::*, util::tabled::print_table};
+use super::util::display::print_mounts;
+use crate::prelude::*;
 use clap::Args;
 
 #[derive(Args)]
@@ -6,15 +7,26 @@ pub(super) struct Mount {
     path: Option<PathBuf>,
     #[clap(short, long)]
     alias: Option<String>,
+    #[clap(
+        short = 'z',
+        long,
+        help = "Timezone for EXIF times without an offset, e.g. +08:00"
+    )]
+    timezone: Option<UtcOffset>,
 }
 
 impl Mount {
-    pub(super) fn run(self) -> Result<()> {
-        let mut mpt = mpt_access_mut();
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
         if let Some(p) = self.path {
-            mpt.insert_or_update(p.resolve(), self.alias);
+            let path = p.resolve()?;
+            let mut mpt = db.mounts_mut();
+            if mpt.insert_or_update(path.clone(), self.alias, self.timezone)? {
+                // so that a failed commit leaves no marker of an unknown volume
+                db.commit()?;
+                db.mounts_mut().mark_volume(&path)?;
+            }
         }
-        print_table(mpt.records());
+        print_mounts(db.mounts().records());
         Ok(())
     }
 }
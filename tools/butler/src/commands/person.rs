@@ -0,0 +1,37 @@
+use super::util::display::print_people;
+use crate::prelude::*;
+use clap::{Args, Subcommand};
+
+#[derive(Args)]
+pub(super) struct Person {
+    #[clap(subcommand)]
+    command: PersonCommand,
+}
+
+#[derive(Subcommand)]
+enum PersonCommand {
+    #[clap(about = "List the clusters of faces found by `butler faces`")]
+    List,
+    #[clap(about = "Name a cluster, clusters of the same name are one person")]
+    Name { cluster: u32, name: String },
+    #[clap(about = "Remove the name of a cluster")]
+    Unname { cluster: u32 },
+}
+
+impl Person {
+    pub(super) fn run(self, _db: &mut Database) -> Result<()> {
+        let mut store = FaceStore::load()?;
+        match self.command {
+            PersonCommand::List => {
+                print_people(&store);
+                return Ok(());
+            }
+            PersonCommand::Name { cluster, name } => {
+                validate_label(&name)?;
+                store.name(cluster, Some(name))?;
+            }
+            PersonCommand::Unname { cluster } => store.name(cluster, None)?,
+        }
+        store.finalize()
+    }
+}
@@ -0,0 +1,20 @@
+use super::util::display::print_mounts;
+use crate::prelude::*;
+use clap::Args;
+
+#[derive(Args)]
+pub(super) struct Remount {
+    #[clap(help = "MPID, its unique prefix, alias or path")]
+    mount: MountSelector,
+    #[clap(help = "Where the mount point is now")]
+    path: PathBuf,
+}
+
+impl Remount {
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        let mut mpt = db.mounts_mut();
+        mpt.relocate(&self.mount, self.path.resolve()?)?;
+        print_mounts(mpt.records());
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+use super::util::labels::LabelCommand;
+use crate::prelude::*;
+use clap::Args;
+
+#[derive(Args)]
+pub(super) struct Tag {
+    #[clap(subcommand)]
+    command: LabelCommand,
+}
+
+impl Tag {
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        self.command.run(db, Label::Tag)
+    }
+}
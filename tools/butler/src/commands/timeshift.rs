@@ -0,0 +1,155 @@
+use super::util::prompt::confirm;
+use crate::prelude::*;
+use crate::util::tabled::print_table;
+use crate::util::time::{parse_duration, parse_time};
+use clap::{ArgGroup, Args};
+use tabled::Tabled;
+
+#[derive(Args)]
+#[clap(group(
+    ArgGroup::new("shift")
+        .required(true)
+        .args(&["by", "reference", "undo"])
+))]
+pub(super) struct Timeshift {
+    #[clap(help = "Photos to shift, e.g. 100..200 or 'mount:sd & date:2021-05'")]
+    queries: Vec<PhotoFilter>,
+    #[clap(
+        short,
+        long,
+        allow_hyphen_values = true,
+        help = "Offset to apply, e.g. +1h30m or -2d"
+    )]
+    by: Option<String>,
+    #[clap(
+        short,
+        long,
+        help = "PID=TIME, the time a photo was actually taken, e.g. 42=2021-05-03T14:00:00"
+    )]
+    reference: Option<String>,
+    #[clap(long, help = "Restore the original EXIF times")]
+    undo: bool,
+    #[clap(short, long, help = "Apply without asking")]
+    yes: bool,
+}
+
+#[derive(Tabled)]
+struct ShiftForDisplay {
+    #[tabled(rename = "PID")]
+    pid: PID,
+    #[tabled(rename = "PATH")]
+    filename: String,
+    #[tabled(rename = "OLD")]
+    old: String,
+    #[tabled(rename = "NEW")]
+    new: String,
+}
+
+struct Shift {
+    pid: PID,
+    etime_shift: i64,
+}
+
+fn mount_tz(tzs: &HashMap<Uuid, FixedOffset>, mpid: &Uuid) -> FixedOffset {
+    tzs.get(mpid)
+        .cloned()
+        .unwrap_or_else(|| UtcOffset::default().fixed())
+}
+
+fn display_time(t: Option<DateTime<Utc>>, tz: &FixedOffset) -> String {
+    t.map(|t| t.with_timezone(tz).to_string())
+        .unwrap_or_else(|| "none".to_string())
+}
+
+impl Timeshift {
+    fn delta(
+        &self,
+        pt: &PhotoTableAccess<'_>,
+        tzs: &HashMap<Uuid, FixedOffset>,
+    ) -> Result<Option<i64>> {
+        if let Some(by) = &self.by {
+            return Ok(Some(parse_duration(by)?.num_seconds()));
+        }
+        let reference = match &self.reference {
+            Some(reference) => reference,
+            None => return Ok(None),
+        };
+        let (pid, time) = reference
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expect PID=TIME for reference, got {}", reference))?;
+        let rec = pt
+            .query(PhotoQuery::from_str(pid)?)
+            .ok_or_else(|| anyhow!("no such photo {}", pid))?;
+        let etime = rec
+            .etime()
+            .ok_or_else(|| anyhow!("{} does not have an EXIF time", rec.pid))?;
+        let actual = parse_time(time, &mount_tz(tzs, &rec.location.mpid))?;
+        Ok(Some((actual - etime).num_seconds()))
+    }
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        if self.queries.is_empty() {
+            bail!("refusing to shift all photos, specify which photos to shift");
+        }
+        let tzs = db
+            .mounts()
+            .records()
+            .map(|mp| (mp.uuid, mp.utc_offset.fixed()))
+            .collect::<HashMap<_, _>>();
+
+        let mut pt = db.photos()?;
+
+        let delta = self.delta(&pt.access(), &tzs)?;
+        let pids = pt.select(&self.queries)?;
+
+        let mut rows = vec![];
+        let mut shifts = vec![];
+        for rec in pt.records_of(pids) {
+            let tz = mount_tz(&tzs, &rec.location.mpid);
+            if rec.overrides.etime.is_some() {
+                warn!("{} has an overridden EXIF time, skipped", rec.pid);
+                continue;
+            }
+            let etime_shift = match delta {
+                Some(d) => rec.etime_shift.checked_add(d),
+                None => Some(0),
+            };
+            let new = etime_shift.and_then(|x| rec.shifted_etime(x));
+            let etime_shift = match etime_shift {
+                Some(x) if new.is_some() || rec.metadata.etime.is_none() => x,
+                _ => bail!("shifting {} goes out of the supported date range", rec.pid),
+            };
+            if etime_shift == rec.etime_shift {
+                continue;
+            }
+            rows.push(ShiftForDisplay {
+                pid: rec.pid,
+                filename: rec.location.filename.display().to_string(),
+                old: display_time(rec.etime(), &tz),
+                new: display_time(new, &tz),
+            });
+            shifts.push(Shift {
+                pid: rec.pid,
+                etime_shift,
+            });
+        }
+
+        if shifts.is_empty() {
+            println!("Nothing to shift.");
+            return Ok(());
+        }
+        print_table(rows);
+        if !self.yes && !confirm(&format!("Shift {} photos?", shifts.len()))? {
+            return Ok(());
+        }
+
+        for shift in shifts {
+            pt.entry(shift.pid)
+                .modify()
+                .unwrap()
+                .with_etime_shift(shift.etime_shift)
+                .commit();
+        }
+        pt.finalize()?;
+        Ok(())
+    }
+}
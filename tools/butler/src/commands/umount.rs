@@ -1,16 +1,77 @@
-use crate::{prelude::*, util::tabled::print_table};
-use clap::Args;
+use super::util::display::print_mounts;
+use super::util::prompt::confirm;
+use crate::prelude::*;
+use clap::{ArgGroup, Args};
 
 #[derive(Args)]
+#[clap(group(ArgGroup::new("orphans").args(&["cascade", "offline"])))]
 pub(super) struct Umount {
-    mpid: Uuid,
+    #[clap(help = "MPID, its unique prefix, alias or path")]
+    mount: MountSelector,
+    #[clap(long, help = "Also remove the records of photos on it")]
+    cascade: bool,
+    #[clap(long, help = "Keep it and its photos, but mark it offline")]
+    offline: bool,
+    #[clap(short, long, help = "Remove without asking")]
+    yes: bool,
 }
 
 impl Umount {
-    pub(super) fn run(self) -> Result<()> {
-        let mut mpt = mpt_access_mut();
-        mpt.entry(self.mpid).remove();
-        print_table(mpt.records());
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        let mp = db.mounts().resolve(&self.mount)?.clone();
+        if self.offline {
+            let mut mpt = db.mounts_mut();
+            mpt.entry(mp.uuid)
+                .modify()
+                .unwrap()
+                .set_offline(true)
+                .commit();
+            print_mounts(mpt.records());
+            return Ok(());
+        }
+
+        let mut pt = db.photos()?;
+        let pids = pt.select(Some(&PhotoFilter::Mount(mp.uuid.into())))?;
+
+        if !pids.is_empty() {
+            let nselected = pt
+                .records_of(pids.iter().cloned())
+                .filter(|rec| rec.selected)
+                .count();
+            let ncommitted = pt
+                .records_of(pids.iter().cloned())
+                .filter(|rec| rec.status != Uncommitted)
+                .count();
+            println!(
+                "{} photos are on {} ({}), {} reviewed, {} selected.",
+                pids.len(),
+                mp.uuid,
+                mp.path,
+                ncommitted,
+                nselected
+            );
+            if !self.cascade {
+                bail!("refusing to umount, use --cascade to remove the photos or --offline to keep them");
+            }
+            if !self.yes && !confirm(&format!("Remove {} photo records?", pids.len()))? {
+                return Ok(());
+            }
+            for pid in pids.iter() {
+                pt.entry(*pid).remove();
+            }
+            pt.finalize()?;
+            // or their faces would still be matched, and undo would try them
+            let mut faces = FaceStore::load()?;
+            if pids.iter().any(|pid| faces.scanned.contains_key(pid)) {
+                pids.iter().for_each(|pid| faces.remove(*pid));
+                faces.finalize()?;
+            }
+            OperationLog::forget(pids);
+        }
+
+        let mut mpt = db.mounts_mut();
+        mpt.entry(mp.uuid).remove();
+        print_mounts(mpt.records());
         Ok(())
     }
 }
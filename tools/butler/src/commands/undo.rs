@@ -0,0 +1,49 @@
+use super::util::display::print_operations;
+use super::util::prompt::confirm;
+use crate::prelude::*;
+use clap::Args;
+
+#[derive(Args)]
+pub(super) struct Undo {
+    #[clap(default_value_t = 1, help = "Operations to undo, latest first")]
+    count: usize,
+    #[clap(short, long, help = "Undo without asking")]
+    yes: bool,
+}
+
+impl Undo {
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        let log = OperationLog::load()?;
+        let ops = log.undoable().take(self.count).collect::<Vec<_>>();
+        if ops.is_empty() {
+            bail!("nothing to undo");
+        }
+        print_operations(ops.iter().cloned());
+        if !self.yes && !confirm(&format!("Undo {} operations?", ops.len()))? {
+            return Ok(());
+        }
+
+        let mut pt = db.photos()?;
+        for op in ops.iter() {
+            for change in op.changes.iter().rev() {
+                let mut rec = unwrap_some_or!(pt.entry(change.pid).modify().ok(), {
+                    warn!("Photo {} is gone, skipped", change.pid);
+                    continue;
+                });
+                let conflicts = rec.revert(change);
+                if !conflicts.is_empty() {
+                    warn!(
+                        "Photo {}: {} changed since operation {}, left as is",
+                        change.pid,
+                        conflicts.join(", "),
+                        op.id
+                    );
+                }
+                rec.commit();
+            }
+        }
+        OperationLog::mark_undone(ops.iter().map(|op| op.id));
+        pt.finalize()?;
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use tabled::Tabled;
 
 use crate::prelude::{yansi::*, *};
-use crate::util::tabled::print_table;
+use crate::util::tabled::{display_option, print_table};
 
 #[derive(Tabled)]
 struct PhotoRecordForDisplay<'a> {
@@ -13,6 +13,10 @@ struct PhotoRecordForDisplay<'a> {
     status: &'a PhotoRecordStatus,
     #[tabled(rename = "Selected", display_with = "display_bool")]
     selected: &'a bool,
+    #[tabled(rename = "Rating", display_with = "display_rating")]
+    rating: &'a u8,
+    #[tabled(rename = "Favorite", display_with = "display_bool")]
+    favorite: &'a bool,
 }
 
 impl<'a> PhotoRecordForDisplay<'a> {
@@ -22,6 +26,8 @@ impl<'a> PhotoRecordForDisplay<'a> {
             location: &rec.location,
             status: &rec.status,
             selected: &rec.selected,
+            rating: &rec.rating,
+            favorite: &rec.favorite,
         }
     }
 }
@@ -34,6 +40,13 @@ fn display_bool(val: &bool) -> String {
     .to_string()
 }
 
+fn display_rating(val: &u8) -> String {
+    match val {
+        0 => Paint::new("-").dimmed().to_string(),
+        n => "*".repeat(*n as usize),
+    }
+}
+
 fn display_location(loc: &Arc<FileLocation>) -> String {
     loc.filename.display().to_string()
 }
@@ -54,3 +67,292 @@ where
 {
     print_table(iter.into_iter().map(PhotoRecordForDisplay::new))
 }
+
+#[derive(Tabled)]
+struct PhotoRecordForLongDisplay<'a> {
+    #[tabled(rename = "PID")]
+    pid: &'a PID,
+    #[tabled(rename = "PATH", display_with = "display_location")]
+    location: &'a Arc<FileLocation>,
+    #[tabled(rename = "Status", display_with = "display_status")]
+    status: &'a PhotoRecordStatus,
+    #[tabled(rename = "Time", display_with = "display_option")]
+    etime: Option<DateTime<Utc>>,
+    #[tabled(rename = "Camera", display_with = "display_option")]
+    device: Option<String>,
+    #[tabled(rename = "Lens", display_with = "display_option")]
+    lens: &'a Option<String>,
+    #[tabled(rename = "Exposure")]
+    exposure: String,
+    #[tabled(rename = "GPS", display_with = "display_option")]
+    gps: &'a Option<GeoPosition>,
+}
+
+pub fn print_photos_long<'a, I>(iter: I)
+where
+    I: IntoIterator<Item = &'a PhotoRecord>,
+{
+    print_table(iter.into_iter().map(|rec| {
+        let camera = &rec.metadata.camera;
+        PhotoRecordForLongDisplay {
+            pid: &rec.pid,
+            location: &rec.location,
+            status: &rec.status,
+            etime: rec.etime(),
+            device: camera.device(),
+            lens: &camera.lens,
+            exposure: camera.exposure(),
+            gps: &rec.metadata.gps,
+        }
+    }))
+}
+
+#[derive(Tabled)]
+struct BurstMemberForDisplay<'a> {
+    #[tabled(rename = "BURST")]
+    burst: usize,
+    #[tabled(rename = "PID")]
+    pid: &'a PID,
+    #[tabled(rename = "PATH", display_with = "display_location")]
+    location: &'a Arc<FileLocation>,
+    #[tabled(rename = "Sharpness")]
+    sharpness: u32,
+    #[tabled(rename = "Best", display_with = "display_bool")]
+    best: bool,
+}
+
+pub fn print_bursts<'a, I>(iter: I)
+where
+    I: IntoIterator<Item = Burst<'a>>,
+{
+    let mut rows = vec![];
+    for (i, burst) in iter.into_iter().enumerate() {
+        let best = burst.best().pid;
+        rows.extend(burst.members.iter().map(|rec| BurstMemberForDisplay {
+            burst: i,
+            pid: &rec.pid,
+            location: &rec.location,
+            sharpness: rec.fingerprint.map(|fp| fp.sharpness).unwrap_or(0),
+            best: rec.pid == best,
+        }));
+    }
+    print_table(rows)
+}
+
+#[derive(Tabled)]
+struct QuarantinedFileForDisplay<'a> {
+    #[tabled(rename = "PID", display_with = "display_option")]
+    pid: Option<PID>,
+    #[tabled(rename = "PATH", display_with = "display_location")]
+    location: &'a Arc<FileLocation>,
+    #[tabled(rename = "ERROR")]
+    error: &'a String,
+    #[tabled(rename = "SINCE")]
+    time: DateTime<Local>,
+}
+
+pub fn print_quarantine(quarantine: &Quarantine) {
+    print_table(
+        quarantine
+            .files
+            .iter()
+            .map(|file| QuarantinedFileForDisplay {
+                pid: file.pid,
+                location: &file.location,
+                error: &file.error,
+                time: file.time.into(),
+            }),
+    )
+}
+
+#[derive(Tabled)]
+struct MountPointForDisplay<'a> {
+    #[tabled(rename = "MPID")]
+    uuid: &'a Uuid,
+    #[tabled(rename = "PATH")]
+    path: &'a CanonicalizedPath,
+    #[tabled(rename = "ALIAS", display_with = "display_option")]
+    alias: &'a Option<String>,
+    #[tabled(rename = "TZ")]
+    utc_offset: &'a UtcOffset,
+    #[tabled(rename = "STATE", display_with = "display_online")]
+    online: bool,
+}
+
+fn display_online(online: &bool) -> String {
+    match online {
+        true => Paint::green("online"),
+        false => Paint::red("offline"),
+    }
+    .to_string()
+}
+
+pub fn print_mounts<'a, I>(iter: I)
+where
+    I: IntoIterator<Item = &'a MountPointRecord>,
+{
+    print_table(iter.into_iter().map(|mp| MountPointForDisplay {
+        uuid: &mp.uuid,
+        path: &mp.path,
+        alias: &mp.alias,
+        utc_offset: &mp.utc_offset,
+        online: mp.is_online(),
+    }))
+}
+
+#[derive(Tabled)]
+struct DayForDisplay {
+    #[tabled(rename = "DATE")]
+    date: NaiveDate,
+    #[tabled(rename = "PHOTOS")]
+    photos: usize,
+    #[tabled(rename = "SELECTED")]
+    selected: usize,
+}
+
+pub fn print_days<'a, I>(iter: I)
+where
+    I: IntoIterator<Item = (NaiveDate, Vec<&'a PhotoRecord>)>,
+{
+    print_table(iter.into_iter().map(|(date, recs)| DayForDisplay {
+        date,
+        photos: recs.len(),
+        selected: recs.iter().filter(|rec| rec.selected).count(),
+    }))
+}
+
+#[derive(Tabled)]
+struct OperationForDisplay<'a> {
+    #[tabled(rename = "ID")]
+    id: u32,
+    #[tabled(rename = "TIME")]
+    time: String,
+    #[tabled(rename = "COMMAND")]
+    command: &'a String,
+    #[tabled(rename = "PHOTOS")]
+    photos: usize,
+    #[tabled(rename = "UNDONE", display_with = "display_bool")]
+    undone: bool,
+}
+
+pub fn print_operations<'a, I>(iter: I)
+where
+    I: IntoIterator<Item = &'a Operation>,
+{
+    print_table(iter.into_iter().map(|op| {
+        OperationForDisplay {
+            id: op.id,
+            time: op
+                .time
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+            command: &op.command,
+            photos: op.changes.len(),
+            undone: op.undone,
+        }
+    }))
+}
+
+#[derive(Tabled)]
+struct FieldChangeForDisplay {
+    #[tabled(rename = "PID")]
+    pid: PID,
+    #[tabled(rename = "FIELD")]
+    field: &'static str,
+    #[tabled(rename = "OLD")]
+    old: String,
+    #[tabled(rename = "NEW")]
+    new: String,
+}
+
+pub fn print_changes(op: &Operation) {
+    print_table(op.changes.iter().flat_map(|change| {
+        change
+            .fields()
+            .into_iter()
+            .map(move |(field, old, new)| FieldChangeForDisplay {
+                pid: change.pid,
+                field,
+                old,
+                new,
+            })
+    }))
+}
+
+#[derive(Tabled)]
+struct LabelForDisplay<'a> {
+    #[tabled(rename = "NAME")]
+    name: &'a str,
+    #[tabled(rename = "PHOTOS")]
+    photos: usize,
+}
+
+pub fn print_labels(label: Label, counts: BTreeMap<&str, usize>) {
+    if counts.is_empty() {
+        println!(
+            "No {} yet.",
+            match label {
+                Label::Tag => "tags",
+                Label::Album => "albums",
+            }
+        );
+        return;
+    }
+    print_table(
+        counts
+            .into_iter()
+            .map(|(name, photos)| LabelForDisplay { name, photos }),
+    )
+}
+
+#[derive(Tabled)]
+struct CaptionForDisplay<'a> {
+    #[tabled(rename = "PID")]
+    pid: &'a PID,
+    #[tabled(rename = "PATH", display_with = "display_location")]
+    location: &'a Arc<FileLocation>,
+    #[tabled(rename = "CAPTION", display_with = "display_option")]
+    caption: &'a Option<String>,
+}
+
+pub fn print_captions<'a, I>(iter: I)
+where
+    I: IntoIterator<Item = &'a PhotoRecord>,
+{
+    print_table(iter.into_iter().map(|rec| CaptionForDisplay {
+        pid: &rec.pid,
+        location: &rec.location,
+        caption: &rec.overrides.caption,
+    }))
+}
+
+#[derive(Tabled)]
+struct ClusterForDisplay<'a> {
+    #[tabled(rename = "CLUSTER")]
+    id: u32,
+    #[tabled(rename = "NAME", display_with = "display_option")]
+    name: &'a Option<String>,
+    #[tabled(rename = "FACES")]
+    faces: u32,
+    #[tabled(rename = "PHOTOS")]
+    photos: usize,
+}
+
+pub fn print_people(store: &FaceStore) {
+    if store.clusters.is_empty() {
+        println!("No faces yet, run `butler faces` first.");
+        return;
+    }
+    print_table(
+        store
+            .clusters
+            .iter()
+            .map(|(id, cluster)| ClusterForDisplay {
+                id: *id,
+                name: &cluster.name,
+                faces: cluster.size,
+                photos: store.photos_in(*id).len(),
+            }),
+    )
+}
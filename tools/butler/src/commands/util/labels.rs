@@ -0,0 +1,66 @@
+use crate::prelude::*;
+use clap::Subcommand;
+
+// shared by `butler tag` and `butler album`
+#[derive(Subcommand)]
+pub enum LabelCommand {
+    #[clap(about = "Add the photos matching the queries")]
+    Add {
+        name: String,
+        #[clap(required = true, help = "Queries like 100..200 or 'date:2021-05'")]
+        queries: Vec<PhotoFilter>,
+    },
+    #[clap(about = "Remove the photos matching the queries, all of them if omitted")]
+    Rm {
+        name: String,
+        queries: Vec<PhotoFilter>,
+    },
+    #[clap(about = "List the names with their numbers of photos")]
+    List,
+}
+
+impl LabelCommand {
+    pub fn run(self, db: &mut Database, label: Label) -> Result<()> {
+        let mut pt = db.photos()?;
+        let (name, queries, add) = match self {
+            LabelCommand::Add { name, queries } => (name, queries, true),
+            LabelCommand::Rm { name, queries } => (name, queries, false),
+            LabelCommand::List => {
+                super::display::print_labels(label, pt.labels(label));
+                return Ok(());
+            }
+        };
+        validate_label(&name)?;
+        let pids = match queries.is_empty() {
+            true => pt.select(Some(&match label {
+                Label::Tag => PhotoFilter::Tag(name.clone()),
+                Label::Album => PhotoFilter::Album(name.clone()),
+            }))?,
+            false => pt.select(&queries)?,
+        };
+
+        let mut nchanged = 0;
+        for pid in pids {
+            let mut rec = unwrap_some_or!(pt.entry(pid).modify().ok(), { continue });
+            let names = match label {
+                Label::Tag => rec.tags_mut(),
+                Label::Album => rec.albums_mut(),
+            };
+            let changed = match add {
+                true => names.insert(name.clone()),
+                false => names.remove(&name),
+            };
+            nchanged += changed as usize;
+            rec.commit();
+        }
+        info!(
+            "{} {} photos {} {:?}",
+            if add { "Added" } else { "Removed" },
+            nchanged,
+            if add { "to" } else { "from" },
+            name
+        );
+        pt.finalize()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+use crate::prelude::*;
+
+pub fn confirm(question: &str) -> Result<bool> {
+    use atty::*;
+    if !is(Stream::Stdin) {
+        bail!("{} Pass --yes to confirm in non-interactive mode", question);
+    }
+    print!("{} (y/n) ", question);
+    std::io::stdout().flush()?;
+    let mut ans = String::new();
+    std::io::stdin().read_line(&mut ans)?;
+    Ok(ans.trim().eq_ignore_ascii_case("y"))
+}
@@ -0,0 +1,183 @@
+use super::generate::Generate;
+use super::index::{ScanOptions, Scanner};
+use super::util::display::print_quarantine;
+use crate::prelude::*;
+use clap::Args;
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+#[derive(Args)]
+pub(super) struct Watch {
+    #[clap(
+        short,
+        long,
+        default_value_t = 2,
+        help = "Seconds to wait for events to settle"
+    )]
+    debounce: u64,
+    #[clap(long, help = "Regenerate images once the selection changes")]
+    regenerate: bool,
+    #[clap(flatten)]
+    generate: Generate,
+}
+
+struct Batch<'a> {
+    db_dir: &'a Path,
+    // files of the tables, written by other butlers
+    tables: &'a [PathBuf],
+    photos: BTreeSet<PathBuf>,
+    rescan: bool,
+    tables_written: bool,
+}
+
+// the photos generate would write out, with when each was committed, so
+// that committing a modified photo again counts as a change too
+type Selection = BTreeMap<PID, Option<DateTime<Utc>>>;
+
+impl<'a> Batch<'a> {
+    fn new(db_dir: &'a Path, tables: &'a [PathBuf]) -> Self {
+        Self {
+            db_dir,
+            tables,
+            photos: BTreeSet::new(),
+            rescan: false,
+            tables_written: false,
+        }
+    }
+    fn is_empty(&self) -> bool {
+        !self.rescan && !self.tables_written && self.photos.is_empty()
+    }
+    fn add(&mut self, event: DebouncedEvent) {
+        use DebouncedEvent::*;
+        let path = match event {
+            Create(p) | Write(p) | Chmod(p) | Remove(p) => p,
+            Rename(from, to) => {
+                self.add(Remove(from));
+                to
+            }
+            Rescan => {
+                self.rescan = true;
+                return;
+            }
+            Error(e, p) => {
+                warn!("Watch error on {:?}: {}", p, e);
+                return;
+            }
+            NoticeWrite(_) | NoticeRemove(_) => return,
+        };
+        // writes to the tables only wake the loop up, to compare selections.
+        // The lock, the journal and staged files are ours, and closing the
+        // lock when going idle would wake the loop up again.
+        if path.parent() != Some(self.db_dir) {
+            self.photos.insert(path);
+        } else if self.tables.contains(&path) {
+            self.tables_written = true;
+        }
+    }
+}
+
+impl Watch {
+    fn selection(&self, pt: &PhotoTableAccess<'_>) -> Selection {
+        pt.records()
+            .filter(|rec| self.generate.wants(rec))
+            .map(|rec| (rec.pid, rec.commit_time))
+            .collect()
+    }
+    fn update(&self, db: &mut Database, batch: Batch<'_>, selection: &mut Selection) -> Result<()> {
+        let mut pt = db.photos()?;
+        if batch.rescan || !batch.photos.is_empty() {
+            let opts = ScanOptions {
+                full: false,
+                strict: false,
+            };
+            let scanner = Scanner::new(&mut pt, opts);
+            let quarantine = if batch.rescan {
+                info!("Rescanning all mount points...");
+                scanner.run()?
+            } else {
+                info!("{} files changed", batch.photos.len());
+                scanner.update_files(batch.photos)?
+            };
+            if !quarantine.files.is_empty() {
+                print_quarantine(&quarantine);
+            }
+        }
+        pt.finalize()?;
+        if batch.rescan {
+            db.mounts_mut().clear_rescan();
+        }
+        db.commit()?;
+        let pt = db.photos()?;
+        let current = self.selection(&pt.access());
+        if self.regenerate && current != *selection {
+            self.generate.generate_from(&pt.access())?;
+        }
+        *selection = current;
+        Ok(())
+    }
+    // watches mount points gone online, and stops watching the ones gone
+    // offline or removed, returns whether any is newly watched
+    fn watch_mounts<W: Watcher>(
+        &self,
+        db: &Database,
+        watcher: &mut W,
+        watched: &mut BTreeSet<CanonicalizedPath>,
+    ) -> Result<bool> {
+        let online = db
+            .mounts()
+            .records()
+            .filter(|mp| mp.is_online())
+            .map(|mp| mp.path.clone())
+            .collect::<BTreeSet<_>>();
+        for path in watched.difference(&online) {
+            info!("No longer watching {}", path);
+            let _ = watcher.unwatch(&**path);
+        }
+        let mut added = false;
+        for path in online.difference(watched) {
+            info!("Watching {}", path);
+            watcher.watch(&**path, RecursiveMode::NonRecursive)?;
+            added = true;
+        }
+        *watched = online;
+        Ok(added)
+    }
+    pub(super) fn run(self, db: &mut Database) -> Result<()> {
+        let (tx, rx) = channel();
+        let mut watcher = notify::watcher(tx, Duration::from_secs(self.debounce))?;
+        let db_dir = Path::new(DEFAULT_DB_DIR);
+        fs::create_dir_all(db_dir)?;
+        let db_dir = db_dir.canonicalize()?;
+        watcher.watch(&db_dir, RecursiveMode::NonRecursive)?;
+        let tables = [
+            DEFAULT_PHOTOS_DB_PATH,
+            DEFAULT_MOUNTPOINTS_DB_PATH,
+            DEFAULT_SQLITE_DB_PATH,
+        ]
+        .iter()
+        .map(|path| db_dir.join(Path::new(path).file_name().unwrap()))
+        .collect::<Vec<_>>();
+        let mut watched = BTreeSet::new();
+        self.watch_mounts(db, &mut watcher, &mut watched)?;
+
+        let mut selection = self.selection(&db.photos()?.access());
+        loop {
+            // let other butlers in while idle
+            lockfile::release();
+            let mut batch = Batch::new(&db_dir, &tables);
+            while batch.is_empty() {
+                batch.add(rx.recv()?);
+                rx.try_iter().for_each(|event| batch.add(event));
+            }
+            lockfile::acquire()?;
+            // as other commands may have written the tables meanwhile
+            db.reload()?;
+            // mount points added meanwhile, with the photos already on them
+            if self.watch_mounts(db, &mut watcher, &mut watched)? {
+                batch.rescan = true;
+            }
+            self.update(db, batch, &mut selection)?;
+        }
+    }
+}
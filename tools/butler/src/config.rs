@@ -0,0 +1,213 @@
+use crate::prelude::*;
+use std::lazy::SyncOnceCell;
+
+// The "butler" section of config.json, every key optional.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub storage: StorageKind,
+    pub faces: Option<FacesConfig>,
+    // a GeoNames cities file to name the places of photos
+    pub geonames: Option<PathBuf>,
+    // images made by `butler generate` for each photo
+    pub renditions: Vec<Rendition>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            storage: Default::default(),
+            faces: None,
+            geonames: None,
+            renditions: Rendition::defaults(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    // one bincode file per table
+    Bincode,
+    // both tables in one SQLite database, see db/storage/sqlite.rs
+    Sqlite,
+}
+
+impl Default for StorageKind {
+    fn default() -> Self {
+        StorageKind::Bincode
+    }
+}
+
+// ONNX models for `butler faces`, see notes/faces.md
+#[derive(Debug, Deserialize)]
+pub struct FacesConfig {
+    pub detector: PathBuf,
+    pub embedder: PathBuf,
+    #[serde(default = "FacesConfig::default_min_score")]
+    pub min_score: f32,
+}
+
+impl FacesConfig {
+    fn default_min_score() -> f32 {
+        0.7
+    }
+}
+
+// A subdirectory of the generated assets, holding one image per photo
+// named after its PID.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rendition {
+    pub name: String,
+    // as [width, height], the full size if omitted
+    #[serde(default)]
+    pub max_size: Option<(u32, u32)>,
+    #[serde(default)]
+    pub format: RenditionFormat,
+    #[serde(default = "Rendition::default_quality")]
+    pub quality: u8,
+    #[serde(default)]
+    pub crop: CropMode,
+    // sigma of an unsharp mask applied after resizing
+    #[serde(default)]
+    pub sharpen: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenditionFormat {
+    Jpeg,
+    Png,
+    // a symlink to the source file
+    Link,
+}
+
+impl Default for RenditionFormat {
+    fn default() -> Self {
+        RenditionFormat::Jpeg
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CropMode {
+    // scaled to fit in max_size, keeping the aspect ratio
+    Fit,
+    // scaled to cover max_size and cropped to it around the center
+    Fill,
+}
+
+impl Default for CropMode {
+    fn default() -> Self {
+        CropMode::Fit
+    }
+}
+
+impl Rendition {
+    fn default_quality() -> u8 {
+        85
+    }
+
+    // the ones the web app was built with
+    pub fn defaults() -> Vec<Self> {
+        let rendition = |name: &str, max_size, format| Self {
+            name: name.to_owned(),
+            max_size,
+            format,
+            quality: Self::default_quality(),
+            crop: CropMode::Fit,
+            sharpen: None,
+        };
+        vec![
+            rendition("s", Some((100, 100)), RenditionFormat::Jpeg),
+            rendition("m", Some((800, 800)), RenditionFormat::Jpeg),
+            rendition("source", None, RenditionFormat::Link),
+        ]
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self.format {
+            RenditionFormat::Png => "png",
+            // sources are linked as .jpg whatever they are, as before
+            RenditionFormat::Jpeg | RenditionFormat::Link => "jpg",
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let name = &self.name;
+        if name.is_empty() || name.starts_with('.') || name.contains(&['/', '\\'][..]) {
+            bail!("invalid rendition name {:?}", name);
+        }
+        if self.format == RenditionFormat::Link
+            && (self.max_size.is_some() || self.crop != CropMode::Fit || self.sharpen.is_some())
+        {
+            bail!("rendition {} links the source, it cannot be resized", name);
+        }
+        if matches!(self.max_size, Some((0, _) | (_, 0))) {
+            bail!("rendition {} has an empty max_size", name);
+        }
+        if self.crop == CropMode::Fill && self.max_size.is_none() {
+            bail!("rendition {} is cropped, it needs a max_size", name);
+        }
+        if !(1..=100).contains(&self.quality) {
+            bail!(
+                "rendition {} has quality {}, expect 1 to 100",
+                name,
+                self.quality
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    butler: Config,
+}
+
+static CONFIG: SyncOnceCell<Config> = SyncOnceCell::new();
+
+// to be called once in the work directory
+pub fn load() -> Result<()> {
+    let file: ConfigFile = serde_json::from_slice(&fs::read("config.json")?)
+        .map_err(|e| anyhow!("Error reading config.json: {}", e))?;
+    let mut names = HashSet::new();
+    for rendition in file.butler.renditions.iter() {
+        rendition.validate()?;
+        if !names.insert(&rendition.name) {
+            bail!("rendition {} is defined twice", rendition.name);
+        }
+    }
+    let _ = CONFIG.set(file.butler);
+    Ok(())
+}
+
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Default::default)
+}
+
+#[test]
+fn test_renditions_config() -> Result<()> {
+    let file: ConfigFile = serde_json::from_str(
+        r#"{"butler": {"renditions": [
+            {"name": "thumb", "max_size": [200, 200], "crop": "fill", "sharpen": 0.5},
+            {"name": "source", "format": "link"}
+        ]}}"#,
+    )?;
+    let renditions = file.butler.renditions;
+    assert_eq!(renditions[0].quality, 85);
+    assert_eq!(renditions[0].crop, CropMode::Fill);
+    assert_eq!(renditions[1].format, RenditionFormat::Link);
+    assert!(renditions.iter().all(|x| x.validate().is_ok()));
+
+    let file: ConfigFile = serde_json::from_str("{}")?;
+    assert_eq!(file.butler.renditions, Rendition::defaults());
+    let linked = Rendition {
+        max_size: Some((100, 100)),
+        ..Rendition::defaults().pop().unwrap()
+    };
+    assert!(linked.validate().is_err());
+    Ok(())
+}
@@ -1,2 +1,12 @@
+pub const DEFAULT_DB_DIR: &'static str = ".butler";
 pub const DEFAULT_PHOTOS_DB_PATH: &'static str = ".butler/photos";
 pub const DEFAULT_MOUNTPOINTS_DB_PATH: &'static str = ".butler/mountpoints";
+pub const DEFAULT_SQLITE_DB_PATH: &'static str = ".butler/butler.sqlite";
+pub const DEFAULT_JOURNAL_PATH: &'static str = ".butler/journal";
+pub const DEFAULT_LOCK_PATH: &'static str = ".butler/lock";
+pub const DEFAULT_OPLOG_PATH: &'static str = ".butler/oplog";
+pub const DEFAULT_FACES_PATH: &'static str = ".butler/faces";
+pub const DEFAULT_QUARANTINE_PATH: &'static str = ".butler/quarantine";
+pub const VOLUME_MARKER_FILENAME: &'static str = ".butler-volume";
+pub const MAX_RATING: u8 = 5;
+pub const DEFAULT_UTC_OFFSET: i32 = 8 * 60 * 60;
@@ -0,0 +1,88 @@
+use super::journal;
+use super::storage::{BincodeStorage, SqliteStorage};
+use crate::config::{config, StorageKind};
+use crate::prelude::*;
+
+// The tables, opened by main and handed to the command run. Photos are
+// loaded on first use, as the commands on mount points do without them.
+pub struct Database {
+    storage: Box<dyn Storage>,
+    mounts: MountPointTable,
+    photos: Option<PhotoTable>,
+}
+
+fn load_mounts(storage: &dyn Storage) -> Result<MountPointTable> {
+    let mut mounts = MountPointTable::new();
+    TableStorage::<MountPointTable>::load(storage, &mut mounts)?;
+    mounts.build_index();
+    Ok(mounts)
+}
+
+impl Database {
+    // to be called with the lock held
+    pub fn open() -> Result<Self> {
+        let storage: Box<dyn Storage> = match config().storage {
+            StorageKind::Bincode => Box::new(BincodeStorage::new(DEFAULT_DB_DIR)),
+            StorageKind::Sqlite => Box::new(SqliteStorage::open(DEFAULT_DB_DIR)?),
+        };
+        journal::recover(
+            &*storage,
+            &[
+                DEFAULT_PHOTOS_DB_PATH,
+                DEFAULT_MOUNTPOINTS_DB_PATH,
+                DEFAULT_OPLOG_PATH,
+                DEFAULT_QUARANTINE_PATH,
+                DEFAULT_FACES_PATH,
+            ],
+        )?;
+        let mounts = load_mounts(&*storage)?;
+        Ok(Self {
+            storage,
+            mounts,
+            photos: None,
+        })
+    }
+    // drops what was loaded, for tables written by others meanwhile
+    pub fn reload(&mut self) -> Result<()> {
+        self.mounts = load_mounts(&*self.storage)?;
+        self.photos = None;
+        Ok(())
+    }
+    pub fn mounts(&self) -> TableAccess<'_, MountPointTable> {
+        TableAccess {
+            table: &self.mounts,
+            context: &(),
+            storage: &*self.storage,
+        }
+    }
+    pub fn mounts_mut(&mut self) -> TableAccessMut<'_, MountPointTable> {
+        TableAccessMut {
+            table: &mut self.mounts,
+            context: &(),
+            storage: &*self.storage,
+        }
+    }
+    // seeing the mount points, which are left as they are meanwhile
+    pub fn photos(&mut self) -> Result<PhotoTableAccessMut<'_>> {
+        if self.photos.is_none() {
+            let mut photos = PhotoTable::new();
+            TableStorage::<PhotoTable>::load(&*self.storage, &mut photos)?;
+            photos.build_index();
+            photos.locate(&self.mounts);
+            self.photos = Some(photos);
+        }
+        Ok(TableAccessMut {
+            table: self.photos.as_mut().unwrap(),
+            context: &self.mounts,
+            storage: &*self.storage,
+        })
+    }
+    // stages the mount points, and commits them with whatever was staged
+    pub fn commit(&mut self) -> Result<()> {
+        self.mounts_mut().finalize()?;
+        journal::commit(&*self.storage)
+    }
+    pub fn discard(&mut self) {
+        journal::discard(&*self.storage);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::db::journal;
+use crate::prelude::*;
+use crate::util::serde::{decode_versioned, save_versioned, Migration, Versioned};
+
+// Faces found by `butler faces`, with their embeddings grouped into
+// clusters, each hopefully one person, named with `butler person name`.
+// Kept apart from the photo table as the embeddings are bulky and only
+// needed by a few commands.
+
+// cosine similarity above which a face joins a cluster
+const SIMILARITY_THRESHOLD: f32 = 0.5;
+
+// in fractions of the upright image
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FaceBox {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Face {
+    pub bbox: FaceBox,
+    pub score: f32,
+    // L2-normalized
+    pub embedding: Vec<f32>,
+    pub cluster: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cluster {
+    pub name: Option<String>,
+    pub size: u32,
+    // of the embeddings of the faces within, or for a named cluster left
+    // empty, the centroid they had, so that the person can be found again
+    sum: Vec<f32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FaceStore {
+    // hashes of the files scanned, so that replaced files are scanned again
+    pub scanned: BTreeMap<PID, FileHash>,
+    pub faces: BTreeMap<PID, Vec<Face>>,
+    pub clusters: BTreeMap<u32, Cluster>,
+    // the last cluster ID given, never reused
+    last_cluster: u32,
+}
+
+impl Versioned for FaceStore {
+    const MAGIC: &'static [u8; 4] = b"BTFC";
+    const VERSION: u32 = 1;
+    const MIGRATIONS: &'static [Migration] = &[];
+}
+
+fn normalize(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0. {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+fn similarity(x: &[f32], y: &[f32]) -> f32 {
+    x.iter().zip(y).map(|(a, b)| a * b).sum()
+}
+
+impl Cluster {
+    fn centroid(&self) -> Vec<f32> {
+        let mut centroid = self.sum.clone();
+        normalize(&mut centroid);
+        centroid
+    }
+}
+
+impl FaceStore {
+    pub fn load() -> Result<Self> {
+        let path = Path::new(DEFAULT_FACES_PATH);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let (store, _) = decode_versioned(path, &fs::read(path)?)?;
+        Ok(store)
+    }
+
+    // only staged, the file is replaced by journal::commit()
+    pub fn finalize(&self) -> Result<()> {
+        journal::stage_file(Path::new(DEFAULT_FACES_PATH), |staged| {
+            save_versioned(self, staged)
+        })
+    }
+
+    pub fn is_scanned(&self, pid: PID, file_hash: FileHash) -> bool {
+        self.scanned.get(&pid) == Some(&file_hash)
+    }
+
+    // the faces found in a photo, replacing those found before
+    pub fn put(&mut self, pid: PID, file_hash: FileHash, found: Vec<(FaceBox, f32, Vec<f32>)>) {
+        self.remove(pid);
+        let faces = found
+            .into_iter()
+            .map(|(bbox, score, mut embedding)| {
+                normalize(&mut embedding);
+                let cluster = self.assign(&embedding);
+                Face {
+                    bbox,
+                    score,
+                    embedding,
+                    cluster,
+                }
+            })
+            .collect::<Vec<_>>();
+        if !faces.is_empty() {
+            self.faces.insert(pid, faces);
+        }
+        self.scanned.insert(pid, file_hash);
+    }
+
+    pub fn remove(&mut self, pid: PID) {
+        self.scanned.remove(&pid);
+        for face in self.faces.remove(&pid).into_iter().flatten() {
+            let cluster = self.clusters.get_mut(&face.cluster).unwrap();
+            let centroid = cluster.centroid();
+            cluster.size -= 1;
+            cluster
+                .sum
+                .iter_mut()
+                .zip(&face.embedding)
+                .for_each(|(s, x)| *s -= x);
+            if cluster.size > 0 {
+                continue;
+            }
+            // named ones are kept, for the person to be recognized again
+            if cluster.name.is_some() {
+                cluster.sum = centroid;
+            } else {
+                self.clusters.remove(&face.cluster);
+            }
+        }
+    }
+
+    // the most similar cluster, or a new one if none is close enough
+    fn assign(&mut self, embedding: &[f32]) -> u32 {
+        let best = self
+            .clusters
+            .iter()
+            .map(|(id, cluster)| (*id, similarity(&cluster.centroid(), embedding)))
+            .filter(|(_, sim)| *sim >= SIMILARITY_THRESHOLD)
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+        let id = match best {
+            Some((id, _)) => id,
+            None => {
+                self.last_cluster += 1;
+                let id = self.last_cluster;
+                self.clusters.insert(
+                    id,
+                    Cluster {
+                        name: None,
+                        size: 0,
+                        sum: vec![0.; embedding.len()],
+                    },
+                );
+                id
+            }
+        };
+        let cluster = self.clusters.get_mut(&id).unwrap();
+        if cluster.size == 0 {
+            cluster.sum.iter_mut().for_each(|s| *s = 0.);
+        }
+        cluster.size += 1;
+        cluster
+            .sum
+            .iter_mut()
+            .zip(embedding)
+            .for_each(|(s, x)| *s += x);
+        id
+    }
+
+    pub fn name(&mut self, id: u32, name: Option<String>) -> Result<()> {
+        let cluster = unwrap_some_or!(self.clusters.get_mut(&id), {
+            bail!("no cluster {}, see `butler person list`", id)
+        });
+        cluster.name = name;
+        Ok(())
+    }
+
+    // clusters given the same name are one person
+    pub fn photos_of(&self, name: &str) -> BTreeSet<PID> {
+        let ids = self
+            .clusters
+            .iter()
+            .filter(|(_, cluster)| cluster.name.as_deref() == Some(name))
+            .map(|(id, _)| *id)
+            .collect::<HashSet<_>>();
+        self.faces
+            .iter()
+            .filter(|(_, faces)| faces.iter().any(|face| ids.contains(&face.cluster)))
+            .map(|(pid, _)| *pid)
+            .collect()
+    }
+
+    pub fn photos_in(&self, id: u32) -> BTreeSet<PID> {
+        self.faces
+            .iter()
+            .filter(|(_, faces)| faces.iter().any(|face| face.cluster == id))
+            .map(|(pid, _)| *pid)
+            .collect()
+    }
+}
+
+#[test]
+fn test_face_clustering() {
+    let mut store = FaceStore::default();
+    let bbox = FaceBox {
+        left: 0.,
+        top: 0.,
+        width: 1.,
+        height: 1.,
+    };
+    store.put(
+        1,
+        10,
+        vec![(bbox, 1., vec![1., 0.1]), (bbox, 1., vec![0., 1.])],
+    );
+    store.put(2, 20, vec![(bbox, 1., vec![2., 0.])]);
+    assert_eq!(store.clusters.len(), 2);
+    store.name(1, Some("grandma".into())).unwrap();
+    assert_eq!(store.photos_of("grandma"), BTreeSet::from([1, 2]));
+
+    // scanned again after the file changed
+    store.put(1, 11, vec![]);
+    assert!(store.is_scanned(1, 11));
+    assert_eq!(store.photos_of("grandma"), BTreeSet::from([2]));
+    assert_eq!(store.clusters.len(), 1);
+
+    // the only face of a named cluster found again
+    store.put(2, 21, vec![]);
+    assert_eq!(store.clusters[&1].size, 0);
+    store.put(2, 22, vec![(bbox, 1., vec![1.5, 0.2])]);
+    assert_eq!(store.photos_of("grandma"), BTreeSet::from([2]));
+    // and IDs of dropped clusters not given again
+    store.put(3, 30, vec![(bbox, 1., vec![0., 1.])]);
+    assert_eq!(store.photos_in(3), BTreeSet::from([3]));
+}
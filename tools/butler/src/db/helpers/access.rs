@@ -1,156 +1,98 @@
 use super::*;
+use crate::db::{Storage, TableStorage};
 use crate::prelude::*;
-use crate::util::serde::TableIO;
-use std::collections::btree_map::Values;
 
-pub struct TableAccess<'a, T>(pub(in crate::db) TableRef<'a, T, &'a T>);
+// Accessors of a table handed out by Database, reading through Deref.
+// Changes go through entries and patches, which keep the index and the
+// changes of the table up to date.
 
-impl<'b, 'a: 'b, T> TableAccess<'a, T>
-where
-    T: Table,
-{
-    pub fn query<K, Q>(&'b self, q: Q) -> Option<&'a <T as Table>::Record>
+pub struct TableAccess<'a, T: Table> {
+    pub(in crate::db) table: &'a T,
+    pub(in crate::db) context: &'a T::Context,
+    pub(in crate::db) storage: &'a dyn Storage,
+}
+
+impl<'a, T: Table> TableAccess<'a, T> {
+    pub fn query<K, Q>(&self, q: Q) -> Option<&'a <T as Table>::Record>
     where
         K: TableKey<T>,
         Q: Borrow<K>,
     {
-        q.borrow()
-            .query_in(unsafe { self.0.as_mut() })
-            .into_mut()
-            .map(|x| &*x)
-    }
-
-    pub fn records(&'b self) -> Values<'a, <T as Table>::PrimaryKey, <T as Table>::Record> {
-        unsafe { self.0.as_mut() }.treemap_mut().values()
+        let key = q.borrow().key_in(self.table, self.context)?;
+        self.table.treemap().get(&key)
     }
 }
 
-impl<'b, 'a: 'b, T> TableAccess<'a, T>
-where
-    T: Table + serde::de::DeserializeOwned + Serialize,
-{
-    pub fn finalize<P: AsRef<Path>>(&'b self, p: P) -> Result<()> {
-        unsafe { self.0.as_mut() }.save_to_path(p)
-    }
-}
+impl<'a, T: Table> Deref for TableAccess<'a, T> {
+    type Target = T;
 
-pub trait TableAccessExt<'a, T>
-where
-    Self: Deref<Target = T> + 'a,
-{
-    fn access(self) -> TableAccess<'a, T>;
-}
-
-impl<'a, T> TableAccessExt<'a, T> for std::sync::MutexGuard<'a, T> {
-    fn access(self) -> TableAccess<'a, T> {
-        TableAccess(TableRef::Guard(self))
+    fn deref(&self) -> &T {
+        self.table
     }
 }
 
-impl<'a, T> TableAccessExt<'a, T> for &'a T {
-    fn access(self) -> TableAccess<'a, T> {
-        TableAccess(TableRef::Ptr(
-            unsafe { std::mem::transmute(self) },
-            PhantomData,
-        ))
-    }
+pub struct TableAccessMut<'a, T: Table> {
+    pub(in crate::db) table: &'a mut T,
+    pub(in crate::db) context: &'a T::Context,
+    pub(in crate::db) storage: &'a dyn Storage,
 }
 
-impl<'b, 'a: 'b, T> TableAccessMut<'a, T>
-where
-    T: Table + serde::de::DeserializeOwned + Serialize,
-{
-    pub fn initialize<P: AsRef<Path>>(&'b self, p: P) -> Result<()> {
-        let table = unsafe { self.0.as_mut() };
-        table.load_from_path(p)?;
-        let treemap = unsafe { self.0.as_mut() }.treemap_mut();
-        table.index_mut().build(treemap);
-        Ok(())
+impl<'a, T: Table> TableAccessMut<'a, T> {
+    pub fn access(&self) -> TableAccess<'_, T> {
+        TableAccess {
+            table: self.table,
+            context: self.context,
+            storage: self.storage,
+        }
     }
-}
-
-pub struct TableAccessMut<'a, T>(pub(in crate::db) TableRef<'a, T, &'a mut T>);
-
-impl<'b, 'a: 'b, T, K: 'a> TableAccessMut<'a, T>
-where
-    T: Table<PrimaryKey = K>,
-    K: Ord + Clone + std::hash::Hash,
-{
-    pub fn retain<F, P>(&'b mut self, mut f: F)
+    pub fn query<K, Q>(&self, q: Q) -> Option<&<T as Table>::Record>
     where
-        TableEntry<'b, 'a, T>: TableEntryTrait<'b, 'a, T, Patch = P>,
-        P: TableRecordPatch<'b, 'a, Table = T>,
-        F: FnMut(&K, &mut P) -> bool,
+        K: TableKey<T>,
+        Q: Borrow<K>,
     {
-        let mut to_remove = HashSet::new();
-        let map = unsafe { self.0.as_mut() }.treemap_mut();
-        for (key, value) in map.iter_mut() {
-            let mut patch = Patch::<'_, '_, T>::new(value, &self.0);
-            if !f(&key, &mut patch) {
-                to_remove.insert(key.clone());
-            }
-            patch.commit();
-        }
-
-        if to_remove.is_empty() {
-            return;
-        }
-
-        let table_index = unsafe { self.0.as_mut() }.index_mut();
-        let map = unsafe { self.0.as_mut() }.treemap_mut();
-        map.retain(|k, rec| {
-            if to_remove.contains(k) {
-                table_index.remove(rec);
-                false
-            } else {
-                true
-            }
-        });
-
-        unsafe { self.0.as_mut() }.modified_flag().set();
+        self.access().query(q)
     }
-}
-
-impl<'b, 'a: 'b, T> TableAccessMut<'a, T>
-where
-    T: Table,
-{
-    pub fn entry<K>(&'b mut self, k: K) -> TableEntry<'b, 'a, T>
+    pub fn entry<K>(&mut self, k: K) -> TableEntry<'_, T>
     where
         K: TableKey<T>,
     {
-        TableEntry::with_key(k, &self.0)
+        let key = k.key_in(self.table, self.context);
+        TableEntry {
+            table: self.table,
+            key,
+        }
     }
-    pub fn insert(&'b mut self, rec: <T as Table>::Record) -> &'b <T as Table>::Record {
-        let table = unsafe { self.0.as_mut() };
-        table.modified_flag().set();
-        table.insert(rec)
+    pub fn clear(&mut self)
+    where
+        <T as Table>::Index: Default,
+    {
+        let (map, index, changes) = self.table.split_mut();
+        *index = Default::default();
+        map.clear();
+        changes.touch_all();
     }
-}
-
-impl<'a, T> Deref for TableAccessMut<'a, T> {
-    type Target = TableAccess<'a, T>;
-
-    fn deref(&self) -> &Self::Target {
-        unsafe { std::mem::transmute(self) }
+    pub fn insert(&mut self, rec: <T as Table>::Record) -> &<T as Table>::Record {
+        self.table.insert(rec)
     }
 }
 
-pub trait TableAccessMutExt<'a, T>
+impl<'a, T: Table> TableAccessMut<'a, T>
 where
-    Self: DerefMut<Target = T> + 'a,
+    dyn Storage + 'a: TableStorage<T>,
 {
-    fn access_mut(self) -> TableAccessMut<'a, T>;
-}
-
-impl<'a, T> TableAccessMutExt<'a, T> for std::sync::MutexGuard<'a, T> {
-    fn access_mut(self) -> TableAccessMut<'a, T> {
-        TableAccessMut(TableRef::Guard(self))
+    // only staged, made durable by Database::commit()
+    pub fn finalize(&mut self) -> Result<()> {
+        self.storage.stage(self.table)?;
+        // as staged, for later changes to be told apart
+        *self.table.changes_mut() = Default::default();
+        Ok(())
     }
 }
 
-impl<'a, T> TableAccessMutExt<'a, T> for &'a mut T {
-    fn access_mut(self) -> TableAccessMut<'a, T> {
-        TableAccessMut(TableRef::Ptr(self, PhantomData))
+impl<'a, T: Table> Deref for TableAccessMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.table
     }
 }
@@ -1,45 +1,34 @@
 use super::*;
-use std::collections::btree_map::Entry;
 
-pub struct TableEntry<'b, 'a: 'b, T: Table> {
-    pub(super) handle: TableHandle<'a, T>,
-    pub(in crate::db) ptr: &'b TableRef<'a, T, &'a mut T>,
+pub struct TableEntry<'a, T: Table> {
+    pub(super) table: &'a mut T,
+    // None if the key stands for no record
+    pub(super) key: Option<T::PrimaryKey>,
 }
 
-impl<'b, 'a: 'b, T: Table> std::fmt::Debug for TableEntry<'b, 'a, T> {
+impl<'a, T: Table> std::fmt::Debug for TableEntry<'a, T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "TableEntry")
     }
 }
 
-pub trait TableEntryTrait<'b, 'a: 'b, T: Table> {
-    type Patch: TableRecordPatch<'b, 'a, Table = T>;
+pub trait TableEntryTrait<'a, T: Table> {
+    type Patch: TableRecordPatch<'a, Table = T>;
 }
 
-pub(super) type Patch<'b, 'a, T> = <TableEntry<'b, 'a, T> as TableEntryTrait<'b, 'a, T>>::Patch;
+pub(super) type Patch<'a, T> = <TableEntry<'a, T> as TableEntryTrait<'a, T>>::Patch;
 
-impl<'b, 'a: 'b, T: Table> TableEntry<'b, 'a, T> {
-    pub(super) fn with_key<K>(k: K, ptr: &'b TableRef<'a, T, &'a mut T>) -> Self
-    where
-        K: TableKey<T>,
-    {
-        let handle = k.query_in(unsafe { ptr.as_mut() });
-        Self { ptr, handle }
-    }
+impl<'a, T: Table> TableEntry<'a, T> {
     pub fn remove(self) -> Option<<T as Table>::Record> {
-        let ptr = unsafe { self.ptr.as_mut() };
-        self.handle.into_occupied().map(|x| {
-            ptr.modified_flag().set();
-            ptr.remove(x)
-        })
+        self.table.remove(&self.key?)
     }
 }
 
-impl<'b, 'a: 'b, T: Table> TableEntry<'b, 'a, T>
+impl<'a, T: Table> TableEntry<'a, T>
 where
-    Self: TableEntryTrait<'b, 'a, T>,
+    Self: TableEntryTrait<'a, T>,
 {
-    pub fn or_insert_with<F>(self, f: F) -> Patch<'b, 'a, T>
+    pub fn or_insert_with<F>(self, f: F) -> Patch<'a, T>
     where
         F: FnOnce() -> <T as Table>::Record,
     {
@@ -47,15 +36,13 @@ where
             Ok(x) => return x,
             Err(x) => x,
         };
-        let rec = f();
-        let rec = unsafe { this.ptr.as_mut() }.insert(rec);
-        unsafe { this.ptr.as_mut() }.modified_flag().set();
-        <Patch<'b, 'a, T>>::new(rec, this.ptr)
+        let key = this.table.insert(f()).primary_key().clone();
+        <Patch<'a, T>>::new(this.table, key)
     }
-    pub fn modify(self) -> std::result::Result<Patch<'b, 'a, T>, Self> {
-        match self.handle.0 {
-            Some(Entry::Occupied(x)) => Ok(<Patch<'b, 'a, T>>::new(x.into_mut(), self.ptr)),
-            _ => Err(self),
+    pub fn modify(self) -> std::result::Result<Patch<'a, T>, Self> {
+        match self.key {
+            Some(key) => Ok(<Patch<'a, T>>::new(self.table, key)),
+            None => Err(self),
         }
     }
 }
@@ -1,13 +1,11 @@
 mod access;
 mod entry;
-mod handle;
 mod index;
 mod patch;
 mod table;
 
 pub use access::*;
 pub use entry::*;
-pub use handle::*;
 pub use index::*;
 pub use patch::*;
 pub use table::*;
@@ -1,10 +1,9 @@
-use super::handle::*;
 use super::table::*;
 
-#[allow(drop_bounds)]
-pub trait TableRecordPatch<'b, 'a>: Drop + Sized {
+pub trait TableRecordPatch<'a>: Sized {
     type Table: Table;
-    fn new(rec: TableRecordMut<'a, Self::Table>, ptr: &'b TableRefMut<'a, Self::Table>) -> Self;
+    // `key` is of a record present in the table
+    fn new(table: &'a mut Self::Table, key: <Self::Table as Table>::PrimaryKey) -> Self;
     fn commit(self) {
         drop(self);
     }
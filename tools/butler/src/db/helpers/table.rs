@@ -1,57 +1,106 @@
-use crate::util::sync::AtomicFlag;
-
-use super::{Occupied, TableHandle, TableIndex};
-use std::collections::btree_map::BTreeMap;
-use std::marker::PhantomData;
-use std::ops::Deref;
+use super::TableIndex;
+use std::borrow::Borrow;
+use std::collections::btree_map::{BTreeMap, Values};
+use std::collections::BTreeSet;
 
 pub trait TableRecord {
     type Table: Table;
     fn primary_key(&self) -> &<Self::Table as Table>::PrimaryKey;
 }
 
+// what changed in a table since it was loaded or last saved
+#[derive(Debug)]
+pub struct Changes<K> {
+    // the table as a whole, e.g. when cleared or migrated
+    pub all: bool,
+    // what precedes the records, like the PID counter
+    pub head: bool,
+    // records inserted, modified or removed
+    pub keys: BTreeSet<K>,
+}
+
+impl<K> Default for Changes<K> {
+    fn default() -> Self {
+        Self {
+            all: false,
+            head: false,
+            keys: BTreeSet::new(),
+        }
+    }
+}
+
+impl<K: Ord> Changes<K> {
+    pub fn is_empty(&self) -> bool {
+        !self.all && !self.head && self.keys.is_empty()
+    }
+    pub fn touch(&mut self, key: K) {
+        if !self.all {
+            self.keys.insert(key);
+        }
+    }
+    pub fn touch_all(&mut self) {
+        self.all = true;
+        self.keys.clear();
+    }
+}
+
 pub trait Table {
     type PrimaryKey: Ord + Clone;
     type Record: TableRecord<Table = Self>;
     type Index: TableIndex<Table = Self>;
+    // what the accessors of the table see besides it, for keys to be
+    // resolved and filters evaluated
+    type Context: 'static;
 
-    fn insert(&mut self, rec: Self::Record) -> &mut Self::Record {
-        self.index_mut().insert(&rec);
-        self.treemap_mut()
-            .entry(rec.primary_key().clone())
-            .or_insert(rec)
-    }
-    fn remove(&mut self, slot: Occupied<'_, Self>) -> Self::Record {
-        self.index_mut().remove(slot.get());
-        slot.remove()
-    }
-    fn treemap_mut(&mut self) -> &mut BTreeMap<Self::PrimaryKey, Self::Record>;
-    fn index_mut(&mut self) -> &mut Self::Index;
-    fn modified_flag(&self) -> &AtomicFlag;
-}
+    fn treemap(&self) -> &BTreeMap<Self::PrimaryKey, Self::Record>;
+    fn changes(&self) -> &Changes<Self::PrimaryKey>;
+    // the parts of the table, to be updated together
+    #[allow(clippy::type_complexity)]
+    fn split_mut(
+        &mut self,
+    ) -> (
+        &mut BTreeMap<Self::PrimaryKey, Self::Record>,
+        &mut Self::Index,
+        &mut Changes<Self::PrimaryKey>,
+    );
 
-pub trait TableKey<T: Table> {
-    fn query_in<'a, 'b>(&'a self, table: &'b mut T) -> TableHandle<'b, T>;
-    fn query_ref_in<'a, 'b>(&'a self, table: &'b mut T) -> Option<&'b <T as Table>::Record> {
-        self.query_in(table).into_mut().map(|x| &*x)
+    fn records(&self) -> Values<'_, Self::PrimaryKey, Self::Record> {
+        self.treemap().values()
+    }
+    fn insert(&mut self, rec: Self::Record) -> &mut Self::Record {
+        let (map, index, changes) = self.split_mut();
+        let key = rec.primary_key().clone();
+        index.insert(&rec);
+        changes.touch(key.clone());
+        map.entry(key).or_insert(rec)
+    }
+    fn remove(&mut self, key: &Self::PrimaryKey) -> Option<Self::Record> {
+        let (map, index, changes) = self.split_mut();
+        let rec = map.remove(key)?;
+        index.remove(&rec);
+        changes.touch(key.clone());
+        Some(rec)
+    }
+    fn build_index(&mut self) {
+        let (map, index, _) = self.split_mut();
+        index.build(map);
+    }
+    fn changes_mut(&mut self) -> &mut Changes<Self::PrimaryKey> {
+        self.split_mut().2
     }
 }
 
-pub enum TableRef<'a, T, M: 'a> {
-    Ptr(*mut T, PhantomData<M>),
-    Guard(std::sync::MutexGuard<'a, T>),
+pub trait TableKey<T: Table> {
+    // the primary key of the record it stands for, None if there is none
+    fn key_in(&self, table: &T, context: &T::Context) -> Option<T::PrimaryKey>;
 }
 
-impl<'b, 'a: 'b, M: 'a, T> TableRef<'a, T, M> {
-    #[inline(always)]
-    #[allow(mutable_transmutes)]
-    pub(in crate::db) unsafe fn as_mut(&'b self) -> &'a mut T {
-        use TableRef::*;
-        match self {
-            Ptr(x, _) => &mut **x,
-            Guard(x) => std::mem::transmute(x.deref()),
-        }
-    }
+// the key of a record present in the table
+pub(in crate::db) fn present<T, K>(table: &T, key: &K) -> Option<T::PrimaryKey>
+where
+    T: Table,
+    K: Borrow<T::PrimaryKey>,
+{
+    let key = key.borrow();
+    table.treemap().contains_key(key).then(|| key.clone())
 }
-
-pub type TableRefMut<'a, T> = TableRef<'a, T, &'a mut T>;
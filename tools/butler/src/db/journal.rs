@@ -0,0 +1,113 @@
+use super::storage::Storage;
+use crate::prelude::*;
+use atomicwrites::{AllowOverwrite, AtomicFile, Error as AtomicFileError};
+
+// Finalized tables are first staged and only made durable by `commit()`,
+// so that a failing command leaves every table untouched. Files, like the
+// bincode tables and the operation log, are written next to where they go.
+// The journal lists the staged files: once the storage has committed it,
+// the commit is decided, and a crash halfway is rolled forward on next
+// start. The SQLite storage commits the journal in the transaction of the
+// rows, so that one COMMIT decides both.
+
+lazy_static! {
+    static ref STAGED: Mutex<Vec<(PathBuf, PathBuf)>> = Mutex::new(vec![]);
+}
+
+fn staged_path(path: &Path) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".staged");
+    s.into()
+}
+
+// writes a file next to `path` with `write`, moved into place by `commit()`
+pub(super) fn stage_file<F>(path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&Path) -> Result<()>,
+{
+    let staged = staged_path(path);
+    write(&staged)?;
+    let mut entries = STAGED.lock().unwrap();
+    if !entries.iter().any(|(_, p)| p == path) {
+        entries.push((staged, path.to_path_buf()));
+    }
+    Ok(())
+}
+
+fn roll_forward(entries: &[(PathBuf, PathBuf)]) -> Result<()> {
+    for (staged, path) in entries {
+        // already moved if we crashed after it
+        if staged.exists() {
+            fs::rename(staged, path)?;
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn write_file_journal(entries: &[(PathBuf, PathBuf)]) -> Result<()> {
+    AtomicFile::new(DEFAULT_JOURNAL_PATH, AllowOverwrite)
+        .write(|file| bincode::serialize_into(BufWriter::new(file), entries))
+        .map_err(|e| match e {
+            AtomicFileError::Internal(e) => e.into(),
+            AtomicFileError::User(e) => e.into(),
+        })
+}
+
+pub(super) fn read_file_journal() -> Result<Option<Vec<(PathBuf, PathBuf)>>> {
+    let journal = Path::new(DEFAULT_JOURNAL_PATH);
+    if !journal.exists() {
+        return Ok(None);
+    }
+    let entries = bincode::deserialize(&fs::read(journal)?)
+        .map_err(|e| anyhow!("Error reading {}: {}", journal.display(), e))?;
+    Ok(Some(entries))
+}
+
+pub(super) fn remove_file_journal() -> Result<()> {
+    let journal = Path::new(DEFAULT_JOURNAL_PATH);
+    if journal.exists() {
+        fs::remove_file(journal)?;
+    }
+    Ok(())
+}
+
+pub(super) fn commit(storage: &dyn Storage) -> Result<()> {
+    OperationLog::stage_pending()?;
+    let mut entries = STAGED.lock().unwrap();
+    storage.commit(&entries)?;
+    roll_forward(&entries)?;
+    if !entries.is_empty() {
+        storage.clear_journals()?;
+    }
+    entries.clear();
+    Ok(())
+}
+
+pub(super) fn discard(storage: &dyn Storage) {
+    storage.discard();
+    OperationLog::discard_pending();
+    for (staged, _) in STAGED.lock().unwrap().drain(..) {
+        let _ = fs::remove_file(staged);
+    }
+}
+
+// to be called with the lock held, before any table is loaded
+pub(super) fn recover<P: AsRef<Path>>(storage: &dyn Storage, paths: &[P]) -> Result<()> {
+    let journals = storage.journals()?;
+    if !journals.is_empty() {
+        warn!("Completing a commit interrupted last time");
+        journals
+            .iter()
+            .try_for_each(|entries| roll_forward(entries))?;
+        storage.clear_journals()?;
+    }
+    // staged but never committed
+    for path in paths {
+        let staged = staged_path(path.as_ref());
+        if staged.exists() {
+            warn!("Discarding uncommitted {}", staged.display());
+            fs::remove_file(staged)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use crate::prelude::*;
+use std::os::unix::io::AsRawFd;
+
+// An advisory lock on the database directory, so that concurrent butler
+// processes (say the picker and `butler index`) take turns instead of
+// clobbering each other's writes. Closing the file releases it.
+
+lazy_static! {
+    static ref LOCK: Mutex<Option<File>> = Mutex::new(None);
+}
+
+fn flock(file: &File, op: libc::c_int) -> bool {
+    unsafe { libc::flock(file.as_raw_fd(), op) == 0 }
+}
+
+pub fn acquire() -> Result<()> {
+    let mut lock = LOCK.lock().unwrap();
+    if lock.is_some() {
+        return Ok(());
+    }
+    let path = Path::new(DEFAULT_LOCK_PATH);
+    fs::create_dir_all(path.parent().unwrap())?;
+    let file = fs::OpenOptions::new().create(true).write(true).open(path)?;
+    if !flock(&file, libc::LOCK_EX | libc::LOCK_NB) {
+        info!("Waiting for another butler to finish...");
+        if !flock(&file, libc::LOCK_EX) {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Cannot lock {}", path.display()));
+        }
+    }
+    *lock = Some(file);
+    Ok(())
+}
+
+pub fn release() {
+    LOCK.lock().unwrap().take();
+}
@@ -1,7 +1,17 @@
+mod database;
+mod faces;
 mod helpers;
+mod journal;
+pub mod lockfile;
 mod mounts;
 mod photos;
+mod quarantine;
+mod storage;
 
+pub use database::*;
+pub use faces::*;
 pub use helpers::*;
 pub use mounts::*;
 pub use photos::*;
+pub use quarantine::*;
+pub use storage::{Storage, StoredTable, TableStorage};
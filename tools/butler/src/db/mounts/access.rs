@@ -1,26 +1,99 @@
+use super::records::{read_volume_id, write_volume_id};
 use crate::prelude::*;
 
-impl<'b, 'a: 'b> TableEntryTrait<'b, 'a, MountPointTable> for TableEntry<'b, 'a, MountPointTable> {
-    type Patch = MountPointRecordPatch<'b, 'a>;
+impl<'a> TableEntryTrait<'a, MountPointTable> for TableEntry<'a, MountPointTable> {
+    type Patch = MountPointRecordPatch<'a>;
 }
 
-impl<'b, 'a: 'b> TableAccessMut<'a, MountPointTable> {
-    pub fn insert_or_update(&'b mut self, path: CanonicalizedPath, alias: Option<String>) {
-        self.entry(path.clone())
-            .or_insert_with(|| MountPointRecord::new(path, None))
-            .set_alias_with(|x| {
-                if alias.is_some() {
-                    *x = alias
+impl<'a> TableAccessMut<'a, MountPointTable> {
+    // returns whether a mount point was added, whose volume is to be
+    // marked with `mark_volume` once committed
+    pub fn insert_or_update(
+        &mut self,
+        path: CanonicalizedPath,
+        alias: Option<String>,
+        utc_offset: Option<UtcOffset>,
+    ) -> Result<bool> {
+        let mut volume_id = None;
+        let inserted = self.query(path.clone()).is_none();
+        if inserted {
+            let marked = read_volume_id(&path);
+            let known =
+                marked.and_then(|id| self.records().find(|mp| mp.volume_id == Some(id)).cloned());
+            match known {
+                // a known volume showing up at another path, e.g. a drive mounted elsewhere
+                Some(mp) if !mp.is_online() => {
+                    info!("{} is a known volume, relocating {} to it", path, mp.uuid);
+                    self.relocate(&mp.uuid.into(), path.clone())?;
+                    volume_id = mp.volume_id;
                 }
-            })
-            .commit();
+                // say the files were copied along with the marker
+                Some(mp) => bail!(
+                    "{} has the volume marker of {}, which is still at {}, \
+                    remove {} from it to mount it as another volume",
+                    path,
+                    mp.uuid,
+                    mp.path,
+                    VOLUME_MARKER_FILENAME
+                ),
+                None => volume_id = Some(marked.unwrap_or_else(Uuid::new_v4)),
+            }
+        }
+        let mut patch = self
+            .entry(path.clone())
+            .or_insert_with(|| MountPointRecord::new(path, None, volume_id));
+        if alias.is_some() {
+            patch = patch.set_alias(alias)?;
+        }
+        if let Some(utc_offset) = utc_offset {
+            patch = patch.set_utc_offset(utc_offset);
+        }
+        patch.set_offline(false).commit();
+        Ok(inserted)
+    }
+    // writes the marker of the volume of a mount point, or forgets the
+    // volume if the marker cannot be written, e.g. on read-only media
+    pub fn mark_volume(&mut self, path: &CanonicalizedPath) -> Result<()> {
+        let rec = self
+            .query(path.clone())
+            .ok_or_else(|| anyhow!("{} is not mounted", path))?;
+        let id = unwrap_some_or!(rec.volume_id, { return Ok(()) });
+        if read_volume_id(path) == Some(id) {
+            return Ok(());
+        }
+        if let Err(e) = write_volume_id(path, id) {
+            warn!("Cannot mark {} as a volume: {}", path, e);
+            let patch = self.entry(path.clone()).modify().unwrap();
+            patch.set_volume_id(None).commit();
+        }
+        Ok(())
+    }
+    // moves a mount point to another path, keeping its MPID and so the photos on it
+    pub fn relocate(&mut self, sel: &MountSelector, path: CanonicalizedPath) -> Result<()> {
+        let old = self.resolve(sel)?.path.clone();
+        if old == path {
+            return Ok(());
+        }
+        if let Some(mp) = self.query(path.clone()) {
+            bail!("{} is already mounted as {}", path, mp.uuid);
+        }
+        let mut rec = self.entry(old).remove().unwrap();
+        rec.path = path;
+        rec.offline = false;
+        self.insert(rec);
+        Ok(())
+    }
+    // after a scan of all mount points online, which read their photos
+    // with the current timezones
+    pub fn clear_rescan(&mut self) {
+        let paths = self
+            .records()
+            .filter(|mp| mp.rescan && mp.is_online())
+            .map(|mp| mp.path.clone())
+            .collect::<Vec<_>>();
+        for path in paths {
+            let patch = self.entry(path).modify().unwrap();
+            patch.set_rescan(false).commit();
+        }
     }
-}
-
-pub fn mpt_access<'a>() -> TableAccess<'a, MountPointTable> {
-    MOUNTPOINT_TABLE.lock().unwrap().access()
-}
-
-pub fn mpt_access_mut<'a>() -> TableAccessMut<'a, MountPointTable> {
-    MOUNTPOINT_TABLE.lock().unwrap().access_mut()
 }
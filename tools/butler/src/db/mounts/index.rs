@@ -3,6 +3,7 @@ use crate::prelude::*;
 #[derive(Default)]
 pub struct MountPointTableIndex {
     pub(super) uuid2path: HashMap<Uuid, CanonicalizedPath>,
+    pub(super) alias2path: HashMap<String, CanonicalizedPath>,
 }
 
 impl TableIndex for MountPointTableIndex {
@@ -10,9 +11,15 @@ impl TableIndex for MountPointTableIndex {
 
     fn remove(&mut self, rec: &MountPointRecord) {
         self.uuid2path.remove(&rec.uuid);
+        if let Some(alias) = &rec.alias {
+            self.alias2path.remove(alias);
+        }
     }
 
     fn insert(&mut self, rec: &MountPointRecord) {
         self.uuid2path.insert(rec.uuid, rec.path.clone());
+        if let Some(alias) = &rec.alias {
+            self.alias2path.insert(alias.clone(), rec.path.clone());
+        }
     }
 }
@@ -2,25 +2,107 @@ use crate::db::*;
 use crate::prelude::*;
 
 impl TableKey<MountPointTable> for Uuid {
-    fn query_in<'a, 'b>(
-        &'a self,
-        table: &'b mut MountPointTable,
-    ) -> TableHandle<'b, MountPointTable> {
-        table
-            .index
-            .uuid2path
-            .get(self.borrow())
-            .cloned()
-            .map(|path| path.query_in(table))
-            .into()
+    fn key_in(&self, table: &MountPointTable, _: &()) -> Option<CanonicalizedPath> {
+        table.index.uuid2path.get(self).cloned()
     }
 }
 
 impl TableKey<MountPointTable> for CanonicalizedPath {
-    fn query_in<'a, 'b>(
-        &'a self,
-        table: &'b mut MountPointTable,
-    ) -> TableHandle<'b, MountPointTable> {
-        Some(table.path2rec.entry(self.clone())).into()
+    fn key_in(&self, table: &MountPointTable, _: &()) -> Option<CanonicalizedPath> {
+        present(table, self)
+    }
+}
+
+// a mount point given by MPID, alias, path or a unique MPID prefix
+#[derive(Clone, Debug)]
+pub enum MountSelector {
+    Mpid(Uuid),
+    Path(CanonicalizedPath),
+    // as typed, any of the above
+    Text(String),
+}
+
+impl MountSelector {
+    pub(super) fn resolve_in(&self, table: &MountPointTable) -> Result<CanonicalizedPath> {
+        let index = &table.index;
+        let s = match self {
+            MountSelector::Mpid(uuid) => {
+                return index
+                    .uuid2path
+                    .get(uuid)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("no mount point {}", uuid))
+            }
+            MountSelector::Path(path) => {
+                return match table.path2rec.contains_key(path) {
+                    true => Ok(path.clone()),
+                    false => Err(anyhow!("{} is not mounted", path)),
+                }
+            }
+            MountSelector::Text(s) => s.as_str(),
+        };
+        if let Ok(uuid) = Uuid::parse_str(s) {
+            return MountSelector::Mpid(uuid).resolve_in(table);
+        }
+        if let Some(path) = index.alias2path.get(s) {
+            return Ok(path.clone());
+        }
+        if let Ok(path) = s.resolve() {
+            if table.path2rec.contains_key(&path) {
+                return Ok(path);
+            }
+        }
+        if s.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+            let prefix = s.to_ascii_lowercase();
+            let mut candidates = index
+                .uuid2path
+                .iter()
+                .filter(|(uuid, _)| uuid.to_string().starts_with(&prefix));
+            match (candidates.next(), candidates.next()) {
+                (Some((_, path)), None) => return Ok(path.clone()),
+                (Some(_), Some(_)) => bail!("MPID prefix {} is ambiguous", s),
+                _ => {}
+            }
+        }
+        bail!("no mount point matches {}", s)
+    }
+}
+
+impl TableKey<MountPointTable> for MountSelector {
+    fn key_in(&self, table: &MountPointTable, _: &()) -> Option<CanonicalizedPath> {
+        self.resolve_in(table).ok()
+    }
+}
+
+impl From<Uuid> for MountSelector {
+    fn from(v: Uuid) -> Self {
+        Self::Mpid(v)
+    }
+}
+
+impl From<CanonicalizedPath> for MountSelector {
+    fn from(v: CanonicalizedPath) -> Self {
+        Self::Path(v)
+    }
+}
+
+impl FromStr for MountSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            bail!("empty mount point selector");
+        }
+        Ok(Self::Text(s.to_string()))
+    }
+}
+
+impl fmt::Display for MountSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mpid(uuid) => write!(f, "{}", uuid),
+            Self::Path(path) => write!(f, "{}", path),
+            Self::Text(s) => write!(f, "{}", s),
+        }
     }
 }
@@ -3,10 +3,11 @@ mod index;
 mod keys;
 mod patch;
 mod records;
+mod schema;
 mod table;
 
-pub use access::*;
 pub use index::*;
+pub use keys::*;
 pub use patch::*;
 pub use records::*;
 pub use table::*;
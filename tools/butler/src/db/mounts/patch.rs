@@ -1,32 +1,91 @@
 use crate::prelude::*;
 
-pub struct MountPointRecordPatch<'b, 'a: 'b> {
-    rec: TableRecordMut<'a, MountPointTable>,
-    _ptr: &'b TableRefMut<'a, MountPointTable>,
+pub struct MountPointRecordPatch<'a> {
+    table: &'a mut MountPointTable,
+    path: CanonicalizedPath,
 }
 
-impl<'b, 'a: 'b> Drop for MountPointRecordPatch<'b, 'a> {
-    fn drop(&mut self) {}
-}
-
-impl<'b, 'a: 'b> TableRecordPatch<'b, 'a> for MountPointRecordPatch<'b, 'a> {
+impl<'a> TableRecordPatch<'a> for MountPointRecordPatch<'a> {
     type Table = MountPointTable;
-    fn new(rec: TableRecordMut<'a, Self::Table>, ptr: &'b TableRefMut<'a, Self::Table>) -> Self {
-        Self { rec, _ptr: ptr }
+    fn new(table: &'a mut MountPointTable, path: CanonicalizedPath) -> Self {
+        Self { table, path }
     }
 }
 
 #[allow(dead_code)]
-impl<'b, 'a: 'b> MountPointRecordPatch<'b, 'a> {
-    pub fn set_alias(self, alias: Option<String>) -> Self {
-        self.rec.alias = alias;
-        self
+impl<'a> MountPointRecordPatch<'a> {
+    fn rec(&self) -> &MountPointRecord {
+        &self.table.path2rec[&self.path]
+    }
+    fn rec_mut(&mut self) -> &mut MountPointRecord {
+        self.table.changes.touch(self.path.clone());
+        self.table.path2rec.get_mut(&self.path).unwrap()
+    }
+    pub fn set_alias(self, alias: Option<String>) -> Result<Self> {
+        if let Some(alias) = &alias {
+            validate_alias(alias)?;
+            match self.table.index.alias2path.get(alias) {
+                Some(path) if path != &self.path => {
+                    bail!("alias {} is already used by {}", alias, path)
+                }
+                _ => {}
+            }
+        }
+        let table = &mut *self.table;
+        let rec = table.path2rec.get_mut(&self.path).unwrap();
+        table.index.remove(rec);
+        rec.alias = alias;
+        table.index.insert(rec);
+        table.changes.touch(self.path.clone());
+        Ok(self)
     }
-    pub fn set_alias_with<F>(self, f: F) -> Self
+    pub fn set_alias_with<F>(self, f: F) -> Result<Self>
     where
         F: FnOnce(&mut Option<String>),
     {
-        f(&mut self.rec.alias);
+        let mut alias = self.rec().alias.clone();
+        f(&mut alias);
+        self.set_alias(alias)
+    }
+    pub fn set_utc_offset(mut self, utc_offset: UtcOffset) -> Self {
+        if self.rec().utc_offset != utc_offset {
+            let rec = self.rec_mut();
+            rec.utc_offset = utc_offset;
+            rec.rescan = true;
+        }
+        self
+    }
+    pub fn set_volume_id(mut self, volume_id: Option<Uuid>) -> Self {
+        self.rec_mut().volume_id = volume_id;
+        self
+    }
+    pub fn set_rescan(mut self, rescan: bool) -> Self {
+        if self.rec().rescan != rescan {
+            self.rec_mut().rescan = rescan;
+        }
+        self
+    }
+    pub fn set_offline(mut self, offline: bool) -> Self {
+        if self.rec().offline != offline {
+            self.rec_mut().offline = offline;
+        }
         self
     }
 }
+
+// aliases show up in photo queries like `mount:sd`, keep them distinguishable
+pub fn validate_alias(alias: &str) -> Result<()> {
+    if alias.is_empty() {
+        bail!("alias cannot be empty");
+    }
+    if Uuid::parse_str(alias).is_ok() {
+        bail!("alias {} looks like an MPID", alias);
+    }
+    if let Some(c) = alias
+        .chars()
+        .find(|c| c.is_whitespace() || "/\\:&|!()".contains(*c))
+    {
+        bail!("alias {} cannot contain {:?}", alias, c);
+    }
+    Ok(())
+}
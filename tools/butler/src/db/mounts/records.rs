@@ -1,16 +1,54 @@
 use crate::prelude::*;
-use crate::util::tabled::*;
 
-use tabled::Tabled;
-
-#[derive(Debug, Clone, Serialize, Deserialize, Tabled)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MountPointRecord {
-    #[tabled(rename = "MPID")]
     pub uuid: Uuid,
-    #[tabled(rename = "PATH")]
     pub path: CanonicalizedPath,
-    #[tabled(rename = "ALIAS", display_with = "display_option")]
     pub alias: Option<String>,
+    pub utc_offset: UtcOffset,
+    // set by `umount --offline`
+    pub offline: bool,
+    // kept in a marker file under the mount point, None if it cannot be written
+    pub volume_id: Option<Uuid>,
+    // set when utc_offset changes, so that the next index reads the times of
+    // its photos again instead of skipping files that look unchanged
+    #[serde(default)]
+    pub rescan: bool,
+}
+
+// seconds east of UTC, used for photos whose EXIF time carries no offset
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UtcOffset(i32);
+
+impl UtcOffset {
+    pub fn fixed(&self) -> FixedOffset {
+        FixedOffset::east(self.0)
+    }
+}
+
+impl Default for UtcOffset {
+    fn default() -> Self {
+        Self(DEFAULT_UTC_OFFSET)
+    }
+}
+
+impl fmt::Display for UtcOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.fixed())
+    }
+}
+
+impl FromStr for UtcOffset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("utc") || s == "Z" {
+            return Ok(Self(0));
+        }
+        crate::util::exif::parse_offset(s)
+            .map(|tz| Self(tz.local_minus_utc()))
+            .ok_or_else(|| anyhow!("cannot parse timezone {}, expect e.g. +08:00", s))
+    }
 }
 
 impl TableRecord for MountPointRecord {
@@ -22,11 +60,41 @@ impl TableRecord for MountPointRecord {
 }
 
 impl MountPointRecord {
-    pub(super) fn new(path: CanonicalizedPath, alias: Option<String>) -> Self {
+    // the marker of `volume_id` is written by the caller once the record
+    // is committed, see `TableAccessMut::mark_volume`
+    pub(super) fn new(
+        path: CanonicalizedPath,
+        alias: Option<String>,
+        volume_id: Option<Uuid>,
+    ) -> Self {
         Self {
             uuid: Uuid::new_v4(),
             path,
             alias,
+            utc_offset: UtcOffset::default(),
+            offline: false,
+            volume_id,
+            rescan: false,
+        }
+    }
+
+    // false if marked offline, or the volume is not there, e.g. an unplugged drive
+    pub fn is_online(&self) -> bool {
+        if self.offline {
+            return false;
+        }
+        match self.volume_id {
+            Some(id) => read_volume_id(&self.path) == Some(id),
+            None => self.path.is_dir(),
         }
     }
 }
+
+pub(super) fn read_volume_id(dir: &Path) -> Option<Uuid> {
+    let content = fs::read_to_string(dir.join(VOLUME_MARKER_FILENAME)).ok()?;
+    Uuid::parse_str(content.trim()).ok()
+}
+
+pub(super) fn write_volume_id(dir: &Path, id: Uuid) -> Result<()> {
+    Ok(fs::write(dir.join(VOLUME_MARKER_FILENAME), id.to_string())?)
+}
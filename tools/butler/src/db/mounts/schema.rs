@@ -0,0 +1,108 @@
+use super::table::MountPointTable;
+use crate::prelude::*;
+use crate::util::serde::{migrate_with, Migration, Versioned};
+use rusqlite::types::Value;
+
+impl Versioned for MountPointTable {
+    const MAGIC: &'static [u8; 4] = b"BTMP";
+    const VERSION: u32 = 1;
+    const MIGRATIONS: &'static [Migration] = &[Migration {
+        from: 0,
+        run: v0_to_v1,
+    }];
+}
+
+impl StoredTable for MountPointTable {
+    const NAME: &'static str = "mountpoints";
+    const KEY_TYPE: &'static str = "TEXT";
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[("uuid", "TEXT"), ("alias", "TEXT")];
+
+    fn sql_key(key: &CanonicalizedPath) -> Value {
+        Value::Text(key.to_string_lossy().into_owned())
+    }
+    fn sql_columns(mp: &MountPointRecord) -> Vec<Value> {
+        vec![
+            Value::Text(mp.uuid.to_string()),
+            mp.alias.clone().map_or(Value::Null, Value::Text),
+        ]
+    }
+}
+
+// Frozen snapshots of past schemas, see db/photos/schema.rs.
+
+// timezone, volume identity and rescan flag added
+pub(super) mod v1 {
+    use crate::prelude::*;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MountPointTable {
+        pub path2rec: BTreeMap<PathBuf, MountPointRecord>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MountPointRecord {
+        pub uuid: Uuid,
+        pub path: PathBuf,
+        pub alias: Option<String>,
+        pub utc_offset: i32,
+        pub offline: bool,
+        pub volume_id: Option<Uuid>,
+        pub rescan: bool,
+    }
+}
+
+pub(super) mod v0 {
+    use crate::prelude::*;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MountPointTable {
+        pub path2rec: BTreeMap<PathBuf, MountPointRecord>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MountPointRecord {
+        pub uuid: Uuid,
+        pub path: PathBuf,
+        pub alias: Option<String>,
+    }
+}
+
+fn v0_to_v1(payload: &[u8]) -> Result<Vec<u8>> {
+    migrate_with(payload, |table: v0::MountPointTable| v1::MountPointTable {
+        path2rec: table
+            .path2rec
+            .into_iter()
+            .map(|(path, mp)| {
+                let mp = v1::MountPointRecord {
+                    uuid: mp.uuid,
+                    path: mp.path,
+                    alias: mp.alias,
+                    utc_offset: DEFAULT_UTC_OFFSET,
+                    offline: false,
+                    // without a marker, it is online as long as the path is there
+                    volume_id: None,
+                    rescan: false,
+                };
+                (path, mp)
+            })
+            .collect(),
+    })
+}
+
+#[test]
+fn test_migrate_from_v0() -> Result<()> {
+    let mp = v0::MountPointRecord {
+        uuid: Uuid::new_v4(),
+        path: "/mnt/photos".into(),
+        alias: Some("nas".into()),
+    };
+    let v0 = v0::MountPointTable {
+        path2rec: [(mp.path.clone(), mp)].into_iter().collect(),
+    };
+    let table: v1::MountPointTable = bincode::deserialize(&v0_to_v1(&bincode::serialize(&v0)?)?)?;
+    let mp = &table.path2rec[Path::new("/mnt/photos")];
+    assert_eq!(mp.alias.as_deref(), Some("nas"));
+    assert_eq!(mp.utc_offset, DEFAULT_UTC_OFFSET);
+    assert!(!mp.offline && mp.volume_id.is_none() && !mp.rescan);
+    Ok(())
+}
@@ -1,43 +1,49 @@
 use crate::prelude::*;
-use crate::util::sync::AtomicFlag;
-
-lazy_static! {
-    pub static ref MOUNTPOINT_TABLE: Mutex<MountPointTable> = Mutex::new(MountPointTable::new());
-}
 
 impl Table for MountPointTable {
     type PrimaryKey = CanonicalizedPath;
     type Record = MountPointRecord;
     type Index = MountPointTableIndex;
+    type Context = ();
 
-    fn index_mut(&mut self) -> &mut MountPointTableIndex {
-        &mut self.index
+    fn treemap(&self) -> &BTreeMap<Self::PrimaryKey, Self::Record> {
+        &self.path2rec
     }
 
-    fn treemap_mut(&mut self) -> &mut BTreeMap<Self::PrimaryKey, Self::Record> {
-        &mut self.path2rec
+    fn changes(&self) -> &Changes<CanonicalizedPath> {
+        &self.changes
     }
 
-    fn modified_flag(&self) -> &AtomicFlag {
-        &self.modified
+    fn split_mut(
+        &mut self,
+    ) -> (
+        &mut BTreeMap<Self::PrimaryKey, Self::Record>,
+        &mut MountPointTableIndex,
+        &mut Changes<CanonicalizedPath>,
+    ) {
+        (&mut self.path2rec, &mut self.index, &mut self.changes)
     }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct MountPointTable {
     #[serde(skip)]
-    modified: AtomicFlag,
+    pub(super) changes: Changes<CanonicalizedPath>,
     pub(super) path2rec: BTreeMap<CanonicalizedPath, MountPointRecord>,
     #[serde(skip)]
     pub(super) index: MountPointTableIndex,
 }
 
 impl MountPointTable {
-    pub(super) fn new() -> MountPointTable {
+    pub(in crate::db) fn new() -> MountPointTable {
         MountPointTable {
-            modified: AtomicFlag::new(),
+            changes: Default::default(),
             index: Default::default(),
             path2rec: BTreeMap::new(),
         }
     }
+    pub fn resolve(&self, sel: &MountSelector) -> Result<&MountPointRecord> {
+        let path = sel.resolve_in(self)?;
+        Ok(&self.path2rec[&path])
+    }
 }
@@ -1,30 +1,25 @@
 use crate::prelude::*;
-
-#[allow(dead_code)]
-pub fn pt_access<'a>() -> TableAccess<'a, PhotoTable> {
-    PHOTO_TABLE.lock().unwrap().access()
-}
-
-pub fn pt_access_mut<'a>() -> TableAccessMut<'a, PhotoTable> {
-    PHOTO_TABLE.lock().unwrap().access_mut()
-}
+use crate::util::sync::AtomicCounter;
 
 #[allow(dead_code)]
 pub type PhotoTableAccess<'a> = TableAccess<'a, PhotoTable>;
 pub type PhotoTableAccessMut<'a> = TableAccessMut<'a, PhotoTable>;
 
-impl<'b, 'a: 'b> TableEntryTrait<'b, 'a, PhotoTable> for TableEntry<'b, 'a, PhotoTable> {
-    type Patch = PhotoRecordPatch<'b, 'a>;
+impl<'a> TableEntryTrait<'a, PhotoTable> for TableEntry<'a, PhotoTable> {
+    type Patch = PhotoRecordPatch<'a>;
 }
 
-impl<'a> TableAccess<'a, PhotoTable> {
+impl PhotoTable {
+    // the next PID to give out
+    pub fn counter(&self) -> PID {
+        self.counter.get()
+    }
     pub fn summary(&self) {
-        let ptr = unsafe { self.0.as_mut() };
-        let ntotal = ptr.pid2rec.len();
-        let nunc = ptr.index.count_status(Uncommitted);
-        let ncsel = ptr.index.count_selected(Committed);
-        let ndsel = ptr.index.count_selected(CommittedButMissing);
-        let nmsel = ptr.index.count_selected(CommittedButModified);
+        let ntotal = self.pid2rec.len();
+        let nunc = self.index.count_status(Uncommitted);
+        let ncsel = self.index.count_selected(Committed);
+        let ndsel = self.index.count_selected(CommittedButMissing);
+        let nmsel = self.index.count_selected(CommittedButModified);
         println!(
             "Among {ntotal} photos, {nc} reviewed.",
             ntotal = ntotal,
@@ -51,8 +46,7 @@ impl<'a> TableAccess<'a, PhotoTable> {
             let mpid = rec.location.mpid;
             let fname = &rec.location.as_ref().filename;
             let exif_time = rec
-                .metadata
-                .etime
+                .etime()
                 .map(|t| t.timestamp().to_string())
                 .unwrap_or_else(|| "none".to_string());
             let ctime = rec.metadata.ctime.timestamp().to_string();
@@ -74,16 +68,80 @@ impl<'a> TableAccess<'a, PhotoTable> {
     }
 }
 
+impl<'a> TableAccess<'a, PhotoTable> {
+    // the mount points, as the photos were located on them
+    pub fn mounts(&self) -> TableAccess<'a, MountPointTable> {
+        TableAccess {
+            table: self.context,
+            context: &(),
+            storage: self.storage,
+        }
+    }
+}
+
 impl<'a> TableAccessMut<'a, PhotoTable> {
-    pub fn take_diff<'b, 'c>(&'b mut self, diff: PhotoRecordDiff<'c>) -> PhotoRecordPatch<'b, 'a>
-    where
-        'a: 'b,
-        'c: 'a,
-    {
+    pub fn mounts(&self) -> TableAccess<'a, MountPointTable> {
+        TableAccess {
+            table: self.context,
+            context: &(),
+            storage: self.storage,
+        }
+    }
+    pub fn take_diff(&mut self, diff: PhotoRecordDiff) -> PhotoRecordPatch<'_> {
         self.entry(diff.pid).modify().unwrap().with_diff(diff)
     }
     pub fn insert_lphoto(&mut self, file: LocalPhoto) -> PID {
         let rec = PhotoRecord::new(0, file);
         self.insert(rec).pid
     }
+    // inserts a record keeping its PID, e.g. when restoring from an export
+    pub fn restore(&mut self, rec: PhotoRecord) -> Result<()> {
+        let table = &mut *self.table;
+        // as the counter is left past it
+        let next = unwrap_some_or!(rec.pid.checked_add(1), {
+            bail!("PID {} is out of range", rec.pid)
+        });
+        if table.pid2rec.contains_key(&rec.pid) {
+            bail!("duplicate PID {}", rec.pid);
+        }
+        if table.index.loc2pid.contains_key(&rec.location) {
+            bail!("{} is listed twice", rec.location.filename.display());
+        }
+        if let Ok(mp) = self.context.resolve(&rec.location.mpid.into()) {
+            rec.location.locate(&mp.path);
+        }
+        if table.counter.get() < next {
+            table.counter = AtomicCounter::new(next);
+            table.changes.head = true;
+        }
+        table.index.insert(&rec);
+        table.changes.touch(rec.pid);
+        table.pid2rec.insert(rec.pid, rec);
+        Ok(())
+    }
+    // never lowered, as PIDs of removed photos would be given out again
+    // and their generated images overwritten
+    pub fn raise_counter(&mut self, counter: PID) {
+        let table = &mut *self.table;
+        if table.counter.get() < counter {
+            table.counter = AtomicCounter::new(counter);
+            table.changes.head = true;
+        }
+    }
+    // keeps the records for which `f` returns true, with what it changed
+    // through the patch committed either way
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&PID, &mut PhotoRecordPatch<'_>) -> bool,
+    {
+        let pids = self.pid2rec.keys().copied().collect::<Vec<_>>();
+        for pid in pids {
+            let mut patch = PhotoRecordPatch::new(self.table, pid);
+            let keep = f(&pid, &mut patch);
+            patch.commit();
+            if !keep {
+                self.table.remove(&pid);
+            }
+        }
+    }
 }
@@ -0,0 +1,45 @@
+use crate::prelude::*;
+
+#[derive(Debug)]
+pub struct Burst<'a> {
+    pub members: Vec<&'a PhotoRecord>,
+}
+
+impl<'a> Burst<'a> {
+    pub fn best(&self) -> &'a PhotoRecord {
+        self.members
+            .iter()
+            .max_by_key(|rec| rec.fingerprint.map(|fp| fp.sharpness).unwrap_or(0))
+            .unwrap()
+    }
+    fn accepts(&self, rec: &PhotoRecord, max_gap: i64, max_distance: u32) -> bool {
+        let last = self.members.last().unwrap();
+        let (t0, t1) = (last.etime().unwrap(), rec.etime().unwrap());
+        if (t1 - t0).num_seconds() > max_gap {
+            return false;
+        }
+        let (fp0, fp1) = (last.fingerprint.unwrap(), rec.fingerprint.unwrap());
+        fp0.distance(&fp1) <= max_distance
+    }
+}
+
+impl PhotoTable {
+    /// Groups photos taken within `max_gap` seconds of each other, and whose
+    /// perceptual hashes differ in at most `max_distance` bits. Photos without
+    /// an EXIF time or a fingerprint never join a burst.
+    pub fn bursts(&self, max_gap: i64, max_distance: u32) -> Vec<Burst<'_>> {
+        let recs = self
+            .records_between(chrono::MIN_DATETIME, chrono::MAX_DATETIME)
+            .filter(|rec| rec.metadata.etime.is_some() && rec.fingerprint.is_some());
+
+        let mut bursts: Vec<Burst<'_>> = vec![];
+        for rec in recs {
+            match bursts.last_mut() {
+                Some(burst) if burst.accepts(rec, max_gap, max_distance) => burst.members.push(rec),
+                _ => bursts.push(Burst { members: vec![rec] }),
+            }
+        }
+        bursts.retain(|burst| burst.members.len() > 1);
+        bursts
+    }
+}
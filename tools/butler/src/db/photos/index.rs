@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use std::ops::RangeBounds;
 
 #[derive(Debug, Default)]
 pub struct PhotoTableIndex {
@@ -6,6 +7,34 @@ pub struct PhotoTableIndex {
     selected_pids: HashSet<u32>,
     status2pids: HashMap<PhotoRecordStatus, HashSet<u32>>,
     mpid2selected_pids: HashMap<Uuid, HashSet<u32>>,
+    mpid2pids: HashMap<Uuid, HashSet<u32>>,
+    // by effective EXIF time, photos without one are left out
+    etime2pids: BTreeMap<DateTime<Utc>, BTreeSet<u32>>,
+    tag2pids: HashMap<String, HashSet<u32>>,
+    album2pids: HashMap<String, HashSet<u32>>,
+    rating2pids: BTreeMap<u8, HashSet<u32>>,
+    favorite_pids: HashSet<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Label {
+    Tag,
+    Album,
+}
+
+impl PhotoTableIndex {
+    fn labels(&self, label: Label) -> &HashMap<String, HashSet<u32>> {
+        match label {
+            Label::Tag => &self.tag2pids,
+            Label::Album => &self.album2pids,
+        }
+    }
+    fn labels_mut(&mut self, label: Label) -> &mut HashMap<String, HashSet<u32>> {
+        match label {
+            Label::Tag => &mut self.tag2pids,
+            Label::Album => &mut self.album2pids,
+        }
+    }
 }
 
 impl PhotoTableIndex {
@@ -33,6 +62,15 @@ impl TableIndex for PhotoTableIndex {
         self.loc2pid.insert(rec.location.clone(), pid);
         self.curate_selected(rec, rec.selected);
         self.status2pids.entry(rec.status).or_default().insert(pid);
+        self.mpid2pids
+            .entry(rec.location.mpid)
+            .or_default()
+            .insert(pid);
+        self.move_etime(pid, None, rec.etime());
+        self.move_labels(Label::Tag, pid, &BTreeSet::new(), &rec.tags);
+        self.move_labels(Label::Album, pid, &BTreeSet::new(), &rec.albums);
+        self.rating2pids.entry(rec.rating).or_default().insert(pid);
+        self.mark_favorite(pid, rec.favorite);
     }
 
     fn remove(&mut self, rec: &PhotoRecord) {
@@ -40,6 +78,15 @@ impl TableIndex for PhotoTableIndex {
         self.loc2pid.remove(&rec.location);
         self.curate_selected(rec, false);
         self.status2pids.entry(rec.status).or_default().remove(&pid);
+        self.mpid2pids
+            .entry(rec.location.mpid)
+            .or_default()
+            .remove(&pid);
+        self.move_etime(pid, rec.etime(), None);
+        self.move_labels(Label::Tag, pid, &rec.tags, &BTreeSet::new());
+        self.move_labels(Label::Album, pid, &rec.albums, &BTreeSet::new());
+        self.rating2pids.entry(rec.rating).or_default().remove(&pid);
+        self.mark_favorite(pid, false);
     }
 }
 
@@ -56,13 +103,106 @@ impl PhotoTableIndex {
     pub(super) fn flip_selected(&mut self, rec: &PhotoRecord) {
         self.curate_selected(rec, rec.selected);
     }
+    pub(super) fn move_etime(
+        &mut self,
+        pid: PID,
+        old_etime: Option<DateTime<Utc>>,
+        new_etime: Option<DateTime<Utc>>,
+    ) {
+        if let Some(t) = old_etime {
+            if let Some(pids) = self.etime2pids.get_mut(&t) {
+                pids.remove(&pid);
+                if pids.is_empty() {
+                    self.etime2pids.remove(&t);
+                }
+            }
+        }
+        if let Some(t) = new_etime {
+            self.etime2pids.entry(t).or_default().insert(pid);
+        }
+    }
+    pub(super) fn move_rating(&mut self, pid: PID, old_rating: u8, new_rating: u8) {
+        self.rating2pids.entry(old_rating).or_default().remove(&pid);
+        self.rating2pids.entry(new_rating).or_default().insert(pid);
+    }
+    pub(super) fn mark_favorite(&mut self, pid: PID, favorite: bool) {
+        if favorite {
+            self.favorite_pids.insert(pid);
+        } else {
+            self.favorite_pids.remove(&pid);
+        }
+    }
+    pub(super) fn move_labels(
+        &mut self,
+        label: Label,
+        pid: PID,
+        old_names: &BTreeSet<String>,
+        new_names: &BTreeSet<String>,
+    ) {
+        let map = self.labels_mut(label);
+        for name in old_names.difference(new_names) {
+            if let Some(pids) = map.get_mut(name) {
+                pids.remove(&pid);
+                if pids.is_empty() {
+                    map.remove(name);
+                }
+            }
+        }
+        for name in new_names.difference(old_names) {
+            map.entry(name.clone()).or_default().insert(pid);
+        }
+    }
 }
 
 impl PhotoTableIndex {
-    pub(super) fn count_status(&mut self, status: PhotoRecordStatus) -> usize {
-        self.status2pids.entry(status).or_default().len()
+    pub(super) fn count_status(&self, status: PhotoRecordStatus) -> usize {
+        self.status2pids.get(&status).map_or(0, |pids| pids.len())
+    }
+    pub(super) fn count_selected(&self, status: PhotoRecordStatus) -> usize {
+        let pids = unwrap_some_or!(self.status2pids.get(&status), { return 0 });
+        pids.iter()
+            .filter(|pid| self.selected_pids.contains(pid))
+            .count()
+    }
+}
+
+impl PhotoTableIndex {
+    pub(super) fn selected(&self) -> impl Iterator<Item = PID> + '_ {
+        self.selected_pids.iter().cloned()
+    }
+    pub(super) fn with_status(&self, status: PhotoRecordStatus) -> impl Iterator<Item = PID> + '_ {
+        self.status2pids.get(&status).into_iter().flatten().cloned()
+    }
+    pub(super) fn on_mount(&self, mpid: &Uuid) -> impl Iterator<Item = PID> + '_ {
+        self.mpid2pids.get(mpid).into_iter().flatten().cloned()
+    }
+    pub(super) fn rated_within<R>(&self, range: R) -> impl Iterator<Item = PID> + '_
+    where
+        R: RangeBounds<u8>,
+    {
+        self.rating2pids
+            .range(range)
+            .flat_map(|(_, pids)| pids.iter().cloned())
+    }
+    pub(super) fn favorites(&self) -> impl Iterator<Item = PID> + '_ {
+        self.favorite_pids.iter().cloned()
+    }
+    pub(super) fn labelled(&self, label: Label, name: &str) -> impl Iterator<Item = PID> + '_ {
+        self.labels(label).get(name).into_iter().flatten().cloned()
+    }
+    pub(super) fn label_counts(&self, label: Label) -> BTreeMap<&str, usize> {
+        self.labels(label)
+            .iter()
+            .map(|(name, pids)| (name.as_str(), pids.len()))
+            .collect()
     }
-    pub(super) fn count_selected(&mut self, status: PhotoRecordStatus) -> usize {
-        (&self.selected_pids & self.status2pids.entry(status).or_default()).len()
+    // in order of time, then PID
+    pub(super) fn taken_within<R>(&self, range: R) -> impl Iterator<Item = PID> + '_
+    where
+        R: RangeBounds<DateTime<Utc>>,
+    {
+        self.etime2pids
+            .range(range)
+            .flat_map(|(_, pids)| pids.iter().cloned())
     }
 }
@@ -1,21 +1,74 @@
 use crate::db::*;
 use crate::prelude::*;
+use std::ops::Bound;
 
 impl TableKey<PhotoTable> for Arc<FileLocation> {
-    fn query_in<'a, 'b>(&'a self, table: &'b mut PhotoTable) -> TableHandle<'b, PhotoTable> {
-        table
-            .index
-            .loc2pid
-            .get(self)
-            .cloned()
-            .map(|pid| pid.query_in(table))
-            .into()
+    fn key_in(&self, table: &PhotoTable, _: &MountPointTable) -> Option<PID> {
+        table.index.loc2pid.get(self).cloned()
     }
 }
 
 impl TableKey<PhotoTable> for PID {
-    fn query_in<'a, 'b>(&'a self, table: &'b mut PhotoTable) -> TableHandle<'b, PhotoTable> {
-        Some(table.pid2rec.entry(self.clone())).into()
+    fn key_in(&self, table: &PhotoTable, _: &MountPointTable) -> Option<PID> {
+        present(table, self)
+    }
+}
+
+// PID range like "100..200", "100..=200", "100.." or "..200"
+#[derive(Clone, Copy, Debug)]
+pub struct PidRange {
+    pub start: Option<PID>,
+    pub end: Option<PID>, // inclusive
+}
+
+impl PidRange {
+    pub fn single(pid: PID) -> Self {
+        Self {
+            start: Some(pid),
+            end: Some(pid),
+        }
+    }
+    // None if the range is empty
+    pub fn bounds(&self) -> Option<(Bound<PID>, Bound<PID>)> {
+        use Bound::*;
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            if start > end {
+                return None;
+            }
+        }
+        Some((
+            self.start.map(Included).unwrap_or(Unbounded),
+            self.end.map(Included).unwrap_or(Unbounded),
+        ))
+    }
+}
+
+impl std::str::FromStr for PidRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once("..")
+            .ok_or_else(|| anyhow!("cannot parse PID range {}", s))?;
+        let (end, inclusive) = match end.strip_prefix('=') {
+            Some(end) => (end, true),
+            None => (end, false),
+        };
+        let parse = |x: &str| -> Result<Option<PID>> {
+            if x.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(u32::from_str(x)?))
+        };
+        let end = match (parse(end)?, inclusive) {
+            (Some(0), false) => bail!("empty PID range {}", s),
+            (Some(x), false) => Some(x - 1),
+            (x, _) => x,
+        };
+        Ok(Self {
+            start: parse(start)?,
+            end,
+        })
     }
 }
 
@@ -23,14 +76,20 @@ impl TableKey<PhotoTable> for PID {
 pub enum PhotoQuery {
     PID(PID),
     FileLocation(Arc<FileLocation>),
+    // canonicalized, of a file on one of the mount points
+    Path(PathBuf),
 }
 
 impl TableKey<PhotoTable> for PhotoQuery {
-    fn query_in<'a, 'b>(&'a self, table: &'b mut PhotoTable) -> TableHandle<'b, PhotoTable> {
+    fn key_in(&self, table: &PhotoTable, mounts: &MountPointTable) -> Option<PID> {
         use PhotoQuery::*;
         match self {
-            PID(x) => x.query_in(table),
-            FileLocation(x) => x.query_in(table),
+            PID(x) => x.key_in(table, mounts),
+            FileLocation(x) => x.key_in(table, mounts),
+            Path(x) => {
+                let loc = Arc::new(crate::locations::FileLocation::from_path(x, mounts).ok()?);
+                loc.key_in(table, mounts)
+            }
         }
     }
 }
@@ -70,7 +129,7 @@ impl std::str::FromStr for PhotoQuery {
             return Ok(v.into());
         }
         if let Some(v) = PathBuf::from_str(s).ok() {
-            return Ok(FileLocation::from_path(v)?.into());
+            return Ok(PhotoQuery::Path(v.canonicalize()?));
         }
         bail!("cannot parse {}", s)
     }
@@ -4,25 +4,240 @@ use crate::util;
 pub type FileHash = u64;
 pub type PID = u32;
 
+// The transform to display an image upright: rotate clockwise by the given
+// degrees, then flip horizontally for the mirrored variants. Mirrored ones
+// are appended so that stored variant indices stay valid.
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub enum PhotoOrientation {
     D0,
     D90,
     D180,
     D270,
+    MirroredD0,
+    MirroredD90,
+    MirroredD180,
+    MirroredD270,
 }
 
-impl From<u32> for PhotoOrientation {
-    fn from(v: u32) -> Self {
+impl PhotoOrientation {
+    pub fn from_exif(v: u32) -> Option<Self> {
         use PhotoOrientation::*;
-        match v {
+        Some(match v {
             0 | 1 => D0,
+            2 => MirroredD0,
             3 => D180,
+            4 => MirroredD180,
+            5 => MirroredD90,
             6 => D90,
+            7 => MirroredD270,
             8 => D270,
-            _ => panic!("unknown orientation value {}", v),
+            _ => return None,
+        })
+    }
+    pub fn swaps_dims(&self) -> bool {
+        use PhotoOrientation::*;
+        matches!(self, D90 | D270 | MirroredD90 | MirroredD270)
+    }
+    pub fn apply(&self, img: image::DynamicImage) -> image::DynamicImage {
+        use PhotoOrientation::*;
+        match self {
+            D0 => img,
+            D90 => img.rotate90(),
+            D180 => img.rotate180(),
+            D270 => img.rotate270(),
+            MirroredD0 => img.fliph(),
+            MirroredD90 => img.rotate90().fliph(),
+            MirroredD180 => img.flipv(),
+            MirroredD270 => img.rotate270().fliph(),
+        }
+    }
+}
+
+// "90" for a clockwise rotation of 90 degrees, "m90" for that followed by a flip
+impl FromStr for PhotoOrientation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use PhotoOrientation::*;
+        Ok(match s {
+            "0" => D0,
+            "90" => D90,
+            "180" => D180,
+            "270" => D270,
+            "m0" => MirroredD0,
+            "m90" => MirroredD90,
+            "m180" => MirroredD180,
+            "m270" => MirroredD270,
+            _ => bail!(
+                "unknown orientation {}, expect one of 0/90/180/270, optionally prefixed by m",
+                s
+            ),
+        })
+    }
+}
+
+#[test]
+fn test_orientation_from_exif() {
+    use PhotoOrientation::*;
+    let all = (1..=8)
+        .map(|v| PhotoOrientation::from_exif(v).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        all,
+        vec![
+            D0,
+            MirroredD0,
+            D180,
+            MirroredD180,
+            MirroredD90,
+            D90,
+            MirroredD270,
+            D270
+        ]
+    );
+    assert_eq!(PhotoOrientation::from_exif(9), None);
+    assert!(MirroredD90.swaps_dims() && !MirroredD180.swaps_dims());
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PhotoFingerprint {
+    pub phash: u64,     // perceptual hash (dHash) of the image
+    pub sharpness: u32, // variance of Laplacian, larger is sharper
+}
+
+impl PhotoFingerprint {
+    pub fn from_image(img: &image::DynamicImage) -> Self {
+        use util::phash::{dhash, laplacian_variance};
+        let thumbnail = img.thumbnail(512, 512);
+        Self {
+            phash: dhash(&thumbnail),
+            sharpness: laplacian_variance(&thumbnail).round() as u32,
+        }
+    }
+    pub fn from_buffer(buf: &[u8]) -> Result<Self> {
+        let img = image::load_from_memory(buf)?;
+        Ok(Self::from_image(&img))
+    }
+    pub fn distance(&self, other: &Self) -> u32 {
+        util::phash::hamming(self.phash, other.phash)
+    }
+}
+
+// in fixed point to keep records comparable, 1e-7 degrees is about 1cm
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct GeoPosition {
+    pub latitude: i32,         // in 1e-7 degrees, north positive
+    pub longitude: i32,        // in 1e-7 degrees, east positive
+    pub altitude: Option<i32>, // in centimeters above sea level
+}
+
+impl GeoPosition {
+    pub fn from_degrees(latitude: f64, longitude: f64, altitude: Option<f64>) -> Option<Self> {
+        if !(-90. ..=90.).contains(&latitude) || !(-180. ..=180.).contains(&longitude) {
+            return None;
+        }
+        Some(Self {
+            latitude: (latitude * 1e7).round() as i32,
+            longitude: (longitude * 1e7).round() as i32,
+            altitude: altitude.map(|x| (x * 100.).round() as i32),
+        })
+    }
+    pub fn latitude(&self) -> f64 {
+        self.latitude as f64 / 1e7
+    }
+    pub fn longitude(&self) -> f64 {
+        self.longitude as f64 / 1e7
+    }
+    pub fn altitude(&self) -> Option<f64> {
+        self.altitude.map(|x| x as f64 / 100.)
+    }
+}
+
+impl fmt::Display for GeoPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.6},{:.6}", self.latitude(), self.longitude())
+    }
+}
+
+// the device and exposure settings, each absent if not in EXIF
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CameraInfo {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub lens: Option<String>,
+    pub focal_length: Option<u32>,         // in 0.1mm
+    pub iso: Option<u32>,                  // PhotographicSensitivity
+    pub exposure_time: Option<(u32, u32)>, // in seconds, as a fraction
+    pub f_number: Option<u32>,             // in 0.1 stops, 18 for f/1.8
+}
+
+impl CameraInfo {
+    pub fn from_exif(exif: &exif::Exif) -> Self {
+        use exif::Tag;
+        use util::exif::{read_rational, read_text, read_u32};
+        let tenths = |tag| read_rational(exif, tag).map(|x| (x.to_f64() * 10.).round() as u32);
+        Self {
+            make: read_text(exif, Tag::Make),
+            model: read_text(exif, Tag::Model),
+            lens: read_text(exif, Tag::LensModel),
+            focal_length: tenths(Tag::FocalLength),
+            iso: read_u32(exif, Tag::PhotographicSensitivity),
+            exposure_time: read_rational(exif, Tag::ExposureTime).map(|x| (x.num, x.denom)),
+            f_number: tenths(Tag::FNumber),
+        }
+    }
+    // make and model, as models often repeat the make
+    pub fn device(&self) -> Option<String> {
+        match (&self.make, &self.model) {
+            (Some(make), Some(model)) if model.starts_with(make.as_str()) => Some(model.clone()),
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.as_ref().or(model.as_ref()).cloned(),
         }
     }
+    // like "24mm 1/125s f/1.8 ISO100", with what is known
+    pub fn exposure(&self) -> String {
+        let mut parts = vec![];
+        if let Some(x) = self.focal_length {
+            parts.push(format!("{}mm", x as f64 / 10.));
+        }
+        match self.exposure_time {
+            Some((num, denom)) if num < denom && num > 0 => {
+                parts.push(format!("1/{}s", (denom as f64 / num as f64).round()))
+            }
+            Some((num, denom)) => parts.push(format!("{}s", num as f64 / denom as f64)),
+            None => {}
+        }
+        if let Some(x) = self.f_number {
+            parts.push(format!("f/{}", x as f64 / 10.));
+        }
+        if let Some(x) = self.iso {
+            parts.push(format!("ISO{}", x));
+        }
+        parts.join(" ")
+    }
+}
+
+#[test]
+fn test_camera_info_display() {
+    let camera = CameraInfo {
+        make: Some("Canon".into()),
+        model: Some("Canon EOS R6".into()),
+        focal_length: Some(240),
+        exposure_time: Some((10, 1250)),
+        f_number: Some(18),
+        iso: Some(100),
+        ..Default::default()
+    };
+    assert_eq!(camera.device().as_deref(), Some("Canon EOS R6"));
+    assert_eq!(camera.exposure(), "24mm 1/125s f/1.8 ISO100");
+    let camera = CameraInfo {
+        make: Some("Apple".into()),
+        model: Some("iPhone 12".into()),
+        exposure_time: Some((2, 1)),
+        ..Default::default()
+    };
+    assert_eq!(camera.device().as_deref(), Some("Apple iPhone 12"));
+    assert_eq!(camera.exposure(), "2s");
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
@@ -36,18 +251,28 @@ pub struct PhotoMetadata {
     pub width: u32,
     pub height: u32,
     pub orientation: PhotoOrientation,
+    pub gps: Option<GeoPosition>,
+    pub camera: CameraInfo,
 }
 
 impl PhotoMetadata {
-    pub fn fix_from(&mut self, other: &Self) {
-        if self.etime.is_some() {
-            println!("already fixed");
-            return;
-        }
-        self.etime = other.etime;
+    // whether the file looks unchanged since we last read it
+    pub fn stat_matches(&self, stat: &fs::Metadata) -> Result<bool> {
+        Ok(self.file_length == stat.len()
+            && self.mtime == DateTime::<Utc>::from(stat.modified()?)
+            && self.ctime == DateTime::<Utc>::from(stat.created()?))
     }
-    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
-        use util::exif::{read_datetime, read_dims, read_orientation};
+    // equal but for the GPS position and camera, which butler did not read before
+    pub fn eq_but_extras(&self, other: &Self) -> bool {
+        *self
+            == Self {
+                gps: self.gps,
+                camera: self.camera.clone(),
+                ..other.clone()
+            }
+    }
+    pub fn from_path<P: AsRef<Path>>(path: P, default_tz: FixedOffset) -> Result<Self> {
+        use util::exif::{read_datetime, read_dims, read_gps_position, read_orientation};
 
         let filepath = path.as_ref();
         let metadata = filepath.metadata()?;
@@ -58,15 +283,27 @@ impl PhotoMetadata {
         let exif_reader = exif::Reader::new();
         let exif = exif_reader.read_from_container(&mut reader);
 
-        let (etime, orientation, width, height) = if let Ok(exif) = exif {
-            let etime = read_datetime(&exif)?;
+        let (etime, orientation, width, height, gps, camera) = if let Ok(exif) = exif {
+            let etime = read_datetime(&exif, default_tz)?;
 
             let (width, height) = read_dims(&exif, &mut reader)?;
-            let orientation = PhotoOrientation::from(read_orientation(&exif));
-            (etime, orientation, width, height)
+            let orientation = read_orientation(&exif);
+            let orientation = PhotoOrientation::from_exif(orientation).unwrap_or_else(|| {
+                warn!(
+                    "{} has unknown orientation {}, assumed upright",
+                    filepath.display(),
+                    orientation
+                );
+                PhotoOrientation::D0
+            });
+            let gps = read_gps_position(&exif)
+                .and_then(|(lat, lon, alt)| GeoPosition::from_degrees(lat, lon, alt));
+            let camera = CameraInfo::from_exif(&exif);
+            (etime, orientation, width, height, gps, camera)
         } else {
             let (width, height) = util::exif::read_dims_from_file(&mut reader)?;
-            (None, PhotoOrientation::D0, width, height)
+            let camera = CameraInfo::default();
+            (None, PhotoOrientation::D0, width, height, None, camera)
         };
 
         Ok(Self {
@@ -77,10 +314,32 @@ impl PhotoMetadata {
             orientation,
             width,
             height,
+            gps,
+            camera,
         })
     }
 }
 
+// manual corrections applied on top of file-derived metadata, which
+// the scanner never touches
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct PhotoOverrides {
+    pub etime: Option<DateTime<Utc>>,
+    pub orientation: Option<PhotoOrientation>,
+    pub caption: Option<String>,
+}
+
+impl PhotoOverrides {
+    pub fn apply_to(&self, metadata: &mut PhotoMetadata) {
+        if self.etime.is_some() {
+            metadata.etime = self.etime;
+        }
+        if let Some(orientation) = self.orientation {
+            metadata.orientation = orientation;
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum PhotoRecordStatus {
     Committed,
@@ -1,14 +1,24 @@
 mod access;
-mod records;
+mod burst;
 mod index;
 mod keys;
 mod misc;
+mod oplog;
 mod patch;
+mod query;
+mod records;
+mod schema;
 mod table;
 
 pub use self::access::*;
-pub use self::records::*;
+pub use self::burst::*;
+pub use self::index::Label;
 pub use self::keys::*;
 pub use self::misc::*;
+pub use self::oplog::*;
 pub use self::patch::*;
+pub use self::query::*;
+pub use self::records::*;
 pub use self::table::*;
+
+pub(super) use self::schema::sql_status;
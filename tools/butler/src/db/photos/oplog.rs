@@ -0,0 +1,122 @@
+use crate::db::journal;
+use crate::prelude::*;
+use crate::util::serde::{decode_versioned, save_versioned, Migration, Versioned};
+
+// History of the changes made through PhotoRecordPatch, one operation per
+// committed run of butler. Records inserted or removed are not logged.
+
+const MAX_OPERATIONS: usize = 100;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: u32,
+    pub command: String,
+    pub time: DateTime<Utc>,
+    pub changes: Vec<PhotoRecordChange>,
+    pub undone: bool,
+}
+
+// oldest first
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OperationLog {
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Default)]
+struct Pending {
+    changes: Vec<PhotoRecordChange>,
+    undone: Vec<u32>,
+    // photos removed, whose changes are dropped from the log
+    removed: BTreeSet<PID>,
+}
+
+lazy_static! {
+    static ref PENDING: Mutex<Pending> = Default::default();
+}
+
+pub(super) fn log_change(change: PhotoRecordChange) {
+    PENDING.lock().unwrap().changes.push(change);
+}
+
+// The log holds record fields, so a field logged by PhotoRecordPatch that
+// is added or changes its type needs a new version here too, besides the
+// one of the photo table.
+impl Versioned for OperationLog {
+    const MAGIC: &'static [u8; 4] = b"BTOL";
+    const VERSION: u32 = 1;
+    const MIGRATIONS: &'static [Migration] = &[];
+}
+
+impl OperationLog {
+    pub fn load() -> Result<Self> {
+        let path = Path::new(DEFAULT_OPLOG_PATH);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let (log, _) = decode_versioned(path, &fs::read(path)?)?;
+        Ok(log)
+    }
+
+    // newest first, operations left without changes by removed photos are
+    // kept as history but skipped
+    pub fn undoable(&self) -> impl Iterator<Item = &Operation> {
+        self.operations
+            .iter()
+            .rev()
+            .filter(|op| !op.undone && !op.changes.is_empty())
+    }
+
+    // marks operations undone once committed, the changes reverting them
+    // are not logged
+    pub fn mark_undone<I: IntoIterator<Item = u32>>(ids: I) {
+        let mut pending = PENDING.lock().unwrap();
+        pending.undone.extend(ids);
+        pending.changes.clear();
+    }
+
+    // drops the changes to photos once their records are removed
+    pub fn forget<I: IntoIterator<Item = PID>>(pids: I) {
+        let mut pending = PENDING.lock().unwrap();
+        pending.removed.extend(pids);
+        let Pending {
+            changes, removed, ..
+        } = &mut *pending;
+        changes.retain(|change| !removed.contains(&change.pid));
+    }
+
+    // staged through the journal with the tables, so that a log failing
+    // to be written fails the run before anything is committed
+    pub(in crate::db) fn stage_pending() -> Result<()> {
+        let mut pending = PENDING.lock().unwrap();
+        if pending.changes.is_empty() && pending.undone.is_empty() && pending.removed.is_empty() {
+            return Ok(());
+        }
+        let mut log = Self::load()?;
+        for op in log.operations.iter_mut() {
+            op.undone |= pending.undone.contains(&op.id);
+            op.changes
+                .retain(|change| !pending.removed.contains(&change.pid));
+        }
+        if !pending.changes.is_empty() {
+            let id = log.operations.last().map(|op| op.id + 1).unwrap_or(1);
+            log.operations.push(Operation {
+                id,
+                command: env::args().skip(1).collect::<Vec<_>>().join(" "),
+                time: Utc::now(),
+                changes: mem::take(&mut pending.changes),
+                undone: false,
+            });
+        }
+        pending.undone.clear();
+        pending.removed.clear();
+        let excess = log.operations.len().saturating_sub(MAX_OPERATIONS);
+        log.operations.drain(..excess);
+        journal::stage_file(Path::new(DEFAULT_OPLOG_PATH), |path| {
+            save_versioned(&log, path)
+        })
+    }
+
+    pub fn discard_pending() {
+        *PENDING.lock().unwrap() = Default::default();
+    }
+}
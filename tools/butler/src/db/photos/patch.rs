@@ -1,77 +1,30 @@
+use super::index::Label;
+use super::oplog;
 use crate::prelude::*;
 use paste::paste;
-use std::borrow::Cow;
-use std::ptr::NonNull;
 
+// a new value of a field, None if left as is
 #[derive(Clone, Debug)]
-pub struct Diff<'a, T: Clone> {
-    old: Cow<'a, T>,
-    new: Option<Cow<'a, T>>,
-}
-
-impl<'a, T: Clone> From<T> for Diff<'a, T> {
-    fn from(v: T) -> Self {
-        Self {
-            old: Cow::Owned(v),
-            new: None,
-        }
-    }
-}
+pub struct Diff<T>(Option<T>);
 
-impl<'a, T: Clone> From<&'a T> for Diff<'a, T> {
-    fn from(v: &'a T) -> Self {
-        Self {
-            old: Cow::Borrowed(v),
-            new: None,
-        }
+impl<T> Default for Diff<T> {
+    fn default() -> Self {
+        Self(None)
     }
 }
 
-impl<'b, 'a: 'b, 'c: 'a, T: Clone> Diff<'a, T> {
-    fn write(&self, slot: &mut T) {
-        self.new.as_ref().map(|v| *slot = v.clone().into_owned());
-    }
-    fn get_mut(&'b mut self) -> &'b mut T {
-        if self.new.is_none() {
-            self.new = Some(self.old.clone());
-        }
-        self.new.as_mut().unwrap().to_mut()
-    }
+impl<T: Clone + PartialEq> Diff<T> {
     pub fn set(&mut self, v: T) {
-        *self.get_mut() = v;
-    }
-    fn current(&self) -> &T {
-        self.new.as_ref().unwrap_or(&self.old)
+        self.0 = Some(v);
     }
-    fn to_owned(&'b self) -> Diff<'c, T> {
-        Diff {
-            old: Cow::Owned(self.old.clone().into_owned()),
-            new: self
-                .new
-                .as_ref()
-                .map(|x| Cow::Owned(x.clone().into_owned())),
-        }
+    fn current<'a>(&'a self, old: &'a T) -> &'a T {
+        self.0.as_ref().unwrap_or(old)
     }
-}
-
-impl<'a, T: Eq + Clone> Diff<'a, T> {
-    fn changed(&self) -> bool {
-        if matches!(self.old, Cow::Borrowed(_)) && matches!(self.new, None | Some(Cow::Borrowed(_)))
-        {
-            return false;
-        }
-        self.new
-            .as_ref()
-            .map(|new| new.ne(&self.old))
-            .unwrap_or(false)
+    fn get_mut(&mut self, old: &T) -> &mut T {
+        self.0.get_or_insert_with(|| old.clone())
     }
-    fn run_if_changed<F>(&self, f: F)
-    where
-        F: FnOnce(&T, &T),
-    {
-        if self.changed() {
-            f(&self.old, &self.new.as_ref().unwrap())
-        }
+    fn changed(&self, old: &T) -> bool {
+        matches!(&self.0, Some(new) if new != old)
     }
 }
 
@@ -80,57 +33,97 @@ macro_rules! fields {
         fields!{@iter $action, $args, [
             (metadata; PhotoMetadata),
             (file_hash; FileHash),
+            (fingerprint; Option<PhotoFingerprint>),
+            (etime_shift; i64),
+            (overrides; PhotoOverrides),
+            (tags; BTreeSet<String>),
+            (albums; BTreeSet<String>),
+            (rating; u8),
+            (favorite; bool),
             (selected; bool),
             (status; PhotoRecordStatus),
             (commit_time; Option<DateTime<Utc>>)
         ]}
     };
     (@iter define_struct, (), [ $(( $N: ident; $T: ty )),+ ]) => {
+        // new values for the fields of a record, applied by take_diff()
         #[derive(Debug)]
-        pub struct PhotoRecordDiff<'a> {
+        pub struct PhotoRecordDiff {
             pub pid: PID,
             is_missing: bool,
             $(
-                pub $N: Diff<'a, $T>,
+                pub $N: Diff<$T>,
             )+
         }
     };
-    (@iter to_owned, ($self: ident), [ $(( $N: ident; $T: ty )),+ ]) => {
-        PhotoRecordDiff {
-            pid: $self.pid,
-            is_missing: $self.is_missing,
-            $( $N: $self.$N.to_owned(), )+
+    (@iter define_change, (), [ $(( $N: ident; $T: ty )),+ ]) => {
+        // the changed fields of a committed patch as (old, new)
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct PhotoRecordChange {
+            pub pid: PID,
+            $(
+                pub $N: Option<($T, $T)>,
+            )+
+        }
+
+        impl PhotoRecordChange {
+            fn is_empty(&self) -> bool {
+                true $( && self.$N.is_none() )+
+            }
+            // (field, old, new) for display
+            pub fn fields(&self) -> Vec<(&'static str, String, String)> {
+                let mut ret = vec![];
+                $(
+                    if let Some((old, new)) = &self.$N {
+                        ret.push((stringify!($N), format!("{:?}", old), format!("{:?}", new)));
+                    }
+                )+
+                ret
+            }
         }
     };
-    (@iter new, ($arg: ident), [ $(( $N: ident; $T: ty )),+ ]) => {
-        Self {
-            pid: $arg.pid,
-            is_missing: false,
-            $( $N: (&$arg.$N).into(), )+
+    (@iter to_change, ($self: ident, $rec: ident), [ $(( $N: ident; $T: ty )),+ ]) => {
+        PhotoRecordChange {
+            pid: $self.pid,
+            $(
+                $N: $self.$N.changed(&$rec.$N).then(|| {
+                    ($rec.$N.clone(), $self.$N.current(&$rec.$N).clone())
+                }),
+            )+
         }
     };
+    (@iter revert, ($self: ident, $change: ident, $conflicts: ident), [ $(( $N: ident; $T: ty )),+ ]) => {
+        $(
+            if let Some((old, new)) = &$change.$N {
+                if $self.$N() == new {
+                    $self.rec_diff.$N.set(old.clone());
+                } else {
+                    $conflicts.push(stringify!($N));
+                }
+            }
+        )+
+    };
     (@iter $action: ident, $args: tt, [ $(( $N: ident; $T: ty )),+ ]) => {
         $(
             fields!(@call $action, $args, $N, $T);
         )+
     };
-    (@call write, ($self: ident, $arg: ident, $changed: ident), $N: ident, $T: ty) => {
-        if $self.$N.changed() {
-            $self.$N.old.to_mut();
-            $self.$N.write(&mut $arg.$N);
-            $changed = true;
+    (@call write, ($change: ident, $rec: ident), $N: ident, $T: ty) => {
+        if let Some((_, new)) = &$change.$N {
+            $rec.$N = new.clone();
         }
     };
     (@call accessor, (), $N: ident, $T: ty) => {
         paste!{
             pub fn $N(&self) -> &$T {
-                self.rec_diff.$N.current()
+                self.rec_diff.$N.current(&self.rec().$N)
             }
             pub fn [<set_ $N>](&mut self, v: $T) {
-                *self.rec_diff.$N.get_mut() = v;
+                self.rec_diff.$N.set(v);
             }
             pub fn [<$N _mut>](&mut self) -> &mut $T {
-                self.rec_diff.$N.get_mut()
+                let rec = &self.table.pid2rec[&self.rec_diff.pid];
+                self.rec_diff.$N.get_mut(&rec.$N)
             }
             pub fn [<with_ $N>](mut self, v: $T) -> Self {
                 self.[<set_ $N>](v);
@@ -140,7 +133,7 @@ macro_rules! fields {
             where
                 F: FnOnce(&mut $T)
             {
-                f(self.rec_diff.$N.get_mut());
+                f(self.[<$N _mut>]());
                 self
             }
         }
@@ -148,94 +141,139 @@ macro_rules! fields {
 }
 
 fields!(define_struct; ());
+fields!(define_change; ());
 
-impl<'a> PhotoRecordDiff<'a> {
-    fn write<'b, 'c>(&'b mut self, rec: &'c mut PhotoRecord) -> (&'c PhotoRecord, bool) {
-        let mut changed = false;
-        fields!(write; (self, rec, changed));
-        (rec, changed)
-    }
-    pub fn new(rec: &'a PhotoRecord) -> Self {
-        fields!(new; (rec))
+impl PhotoRecordDiff {
+    pub fn new(pid: PID) -> Self {
+        Self {
+            pid,
+            is_missing: false,
+            metadata: Default::default(),
+            file_hash: Default::default(),
+            fingerprint: Default::default(),
+            etime_shift: Default::default(),
+            overrides: Default::default(),
+            tags: Default::default(),
+            albums: Default::default(),
+            rating: Default::default(),
+            favorite: Default::default(),
+            selected: Default::default(),
+            status: Default::default(),
+            commit_time: Default::default(),
+        }
     }
-    fn is_dirty(&self) -> bool {
-        self.file_hash.changed() || self.metadata.changed()
+    fn is_dirty(&self, rec: &PhotoRecord) -> bool {
+        // GPS or camera newly read from an unchanged file, as by `index --full`
+        // after upgrading, is no modification
+        let metadata = &self.metadata;
+        self.file_hash.changed(&rec.file_hash)
+            || (metadata.changed(&rec.metadata)
+                && !rec.metadata.eq_but_extras(metadata.current(&rec.metadata)))
     }
-    fn to_owned<'c: 'a>(&self) -> PhotoRecordDiff<'c> {
-        fields!(to_owned; (self))
+    fn to_change(&self, rec: &PhotoRecord) -> PhotoRecordChange {
+        fields!(to_change; (self, rec))
     }
 }
 
-pub struct PhotoRecordPatch<'b, 'a: 'b> {
+pub struct PhotoRecordPatch<'a> {
     commit_at_drop: bool,
-    rec_diff: PhotoRecordDiff<'a>,
-    rec: NonNull<PhotoRecord>,
-    ptr: &'b TableRefMut<'a, PhotoTable>,
+    // written as set, without updating status and commit time
+    verbatim: bool,
+    rec_diff: PhotoRecordDiff,
+    table: &'a mut PhotoTable,
 }
 
 #[allow(dead_code)]
-impl<'b, 'a: 'b> PhotoRecordPatch<'b, 'a> {
+impl<'a> PhotoRecordPatch<'a> {
     fields!(accessor; ());
-    pub(super) fn with_diff<'c: 'a>(mut self, diff: PhotoRecordDiff<'c>) -> Self {
+    fn rec(&self) -> &PhotoRecord {
+        &self.table.pid2rec[&self.rec_diff.pid]
+    }
+    pub(super) fn with_diff(mut self, diff: PhotoRecordDiff) -> Self {
         self.rec_diff = diff;
         self
     }
     pub fn mark_missing(&mut self) {
         self.rec_diff.is_missing = true
     }
-    pub fn into_diff<'c>(mut self) -> PhotoRecordDiff<'c> {
-        self.commit_at_drop = false;
-        let ret = self.rec_diff.to_owned();
-        drop(self);
-        ret
+    // undoes a change, returns the fields changed since and left as they are
+    pub fn revert(&mut self, change: &PhotoRecordChange) -> Vec<&'static str> {
+        let mut conflicts = vec![];
+        fields!(revert; (self, change, conflicts));
+        self.verbatim = true;
+        conflicts
     }
 }
 
-impl<'b, 'a: 'b> Drop for PhotoRecordPatch<'b, 'a> {
+impl<'a> Drop for PhotoRecordPatch<'a> {
     fn drop(&mut self) {
         if !self.commit_at_drop {
             return;
         }
-        let ptr = unsafe { self.ptr.as_mut() };
 
-        if *self.selected() && self.rec_diff.is_missing {
-            self.status_mut().handle_local_missing();
-        } else {
-            let is_dirty = self.rec_diff.is_dirty();
-            self.status_mut().handle_dirty_mark(is_dirty);
-        }
-        {
-            let status = &self.rec_diff.status;
-            if status.changed() && *status.current() == Committed {
+        if !self.verbatim {
+            if *self.selected() && self.rec_diff.is_missing {
+                self.status_mut().handle_local_missing();
+            } else {
+                let is_dirty = self.rec_diff.is_dirty(self.rec());
+                self.status_mut().handle_dirty_mark(is_dirty);
+            }
+            let rec = self.rec();
+            let status = *self.status();
+            // a new orientation invalidates generated images, so we bump commit time
+            let reoriented = self.overrides().orientation != rec.overrides.orientation;
+            if (status != rec.status || reoriented) && status == Committed {
                 self.set_commit_time(Some(Utc::now()))
             }
         }
 
-        let (rec, changed) = self.rec_diff.write(unsafe { self.rec.as_mut() });
-
-        if changed {
-            unsafe { self.ptr.as_mut() }.modified_flag().set();
+        let change = self.rec_diff.to_change(self.rec());
+        if change.is_empty() {
+            return;
         }
+        let table = &mut *self.table;
+        let pid = change.pid;
+        let rec = table.pid2rec.get_mut(&pid).unwrap();
+        let old_etime = rec.etime();
+        fields!(write; (change, rec));
+        table.changes.touch(pid);
 
-        self.rec_diff.selected.run_if_changed(|_o, _n| {
-            ptr.index.flip_selected(rec);
-        });
+        let index = &mut table.index;
+        if change.selected.is_some() {
+            index.flip_selected(rec);
+        }
+        if let Some((o, n)) = &change.status {
+            index.mutate_status(pid, *o, *n);
+        }
+        if let Some((o, n)) = &change.tags {
+            index.move_labels(Label::Tag, pid, o, n);
+        }
+        if let Some((o, n)) = &change.albums {
+            index.move_labels(Label::Album, pid, o, n);
+        }
+        if let Some((o, n)) = &change.rating {
+            index.move_rating(pid, *o, *n);
+        }
+        if let Some((_, n)) = &change.favorite {
+            index.mark_favorite(pid, *n);
+        }
+        // the effective time follows metadata, shift and overrides
+        if rec.etime() != old_etime {
+            index.move_etime(pid, old_etime, rec.etime());
+        }
 
-        self.rec_diff.status.run_if_changed(|o, n| {
-            ptr.index.mutate_status(rec.pid, o.clone(), n.clone());
-        });
+        oplog::log_change(change);
     }
 }
 
-impl<'b, 'a: 'b> TableRecordPatch<'b, 'a> for PhotoRecordPatch<'b, 'a> {
+impl<'a> TableRecordPatch<'a> for PhotoRecordPatch<'a> {
     type Table = PhotoTable;
-    fn new(rec: TableRecordMut<'a, Self::Table>, ptr: &'b TableRefMut<'a, Self::Table>) -> Self {
-        let rec_ptr = unsafe { NonNull::new(std::mem::transmute(rec as *const _)).unwrap() };
+    fn new(table: &'a mut PhotoTable, pid: PID) -> Self {
         Self {
             commit_at_drop: true,
-            rec_diff: PhotoRecordDiff::new(rec),
-            rec: rec_ptr,
-            ptr,
+            verbatim: false,
+            rec_diff: PhotoRecordDiff::new(pid),
+            table,
         }
     }
 }
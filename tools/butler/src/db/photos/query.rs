@@ -0,0 +1,624 @@
+use super::table::PhotoTable;
+use crate::geocoder::geocoder;
+use crate::prelude::*;
+use std::ops::Bound::{self, *};
+use std::ops::RangeBounds;
+use std::ops::RangeInclusive;
+
+// A filter expression over the photo table, e.g.
+//
+//     mount:sd & status:missing    (MPID, its prefix, alias or path)
+//     100..200 | date:2021-05 & !selected
+//     (name:IMG_*.jpg or name:DSC*) and not status:untracked
+//     album:"Travel 2021" & !tag:blurry
+//     rating:4+ or favorite                (rating:3 for exactly 3 stars)
+//     person:grandma & !person:"Uncle Bob"
+//     place:Paris | place:JP               (the nearest city, or its country code)
+//     camera:iphone & iso:..200 | lens:"EF 50mm" & focal:35..85
+//
+// Juxtaposed terms are ANDed, AND binds tighter than OR.
+#[derive(Clone, Debug)]
+pub enum PhotoFilter {
+    Pids(PidRange),
+    // canonicalized, of a file on one of the mount points
+    Location(PathBuf),
+    Mount(MountSelector),
+    Status(PhotoRecordStatus),
+    Selected,
+    Favorite,
+    // the least and the most stars, both inclusive
+    Rating(u8, u8),
+    Date(DateRange),
+    Name(glob::Pattern),
+    Tag(String),
+    Album(String),
+    Person(String),
+    Place(String),
+    // part of the make and model, or of the lens, ignoring case
+    Camera(String),
+    Lens(String),
+    Iso(RangeInclusive<u32>),
+    FocalLength(RangeInclusive<u32>), // in 0.1mm
+    Not(Box<PhotoFilter>),
+    And(Box<PhotoFilter>, Box<PhotoFilter>),
+    Or(Box<PhotoFilter>, Box<PhotoFilter>),
+}
+
+// local dates in the timezone of the mount point, end exclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateRange {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start.map(|x| x <= date).unwrap_or(true) && self.end.map(|x| date < x).unwrap_or(true)
+    }
+}
+
+// "2021", "2021-05" or "2021-05-03" as [first day, day after the last)
+fn parse_period(s: &str) -> Result<(NaiveDate, NaiveDate)> {
+    let parts = s
+        .split('-')
+        .map(u32::from_str)
+        .collect::<Either<Vec<_>, _>>()
+        .with_context(|| format!("cannot parse date {}", s))?;
+    // None for years out of range, even those just past it
+    let ymd = |y: u32, m, d| NaiveDate::from_ymd_opt(i32::try_from(y).ok()?, m, d);
+    let next = |x: u32| x.checked_add(1);
+    let period = match parts[..] {
+        [y] => ymd(y, 1, 1).zip(next(y).and_then(|y| ymd(y, 1, 1))),
+        [y, 12] => ymd(y, 12, 1).zip(next(y).and_then(|y| ymd(y, 1, 1))),
+        [y, m] => ymd(y, m, 1).zip(next(m).and_then(|m| ymd(y, m, 1))),
+        [y, m, d] => ymd(y, m, d).zip(ymd(y, m, d).and_then(|x| x.succ_opt())),
+        _ => None,
+    };
+    period.ok_or_else(|| anyhow!("cannot parse date {}", s))
+}
+
+impl FromStr for DateRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = match s.split_once("..") {
+            Some((start, end)) => (start, end),
+            None => (s, s),
+        };
+        let bound = |x: &str| -> Result<Option<(NaiveDate, NaiveDate)>> {
+            if x.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(parse_period(x)?))
+        };
+        Ok(Self {
+            start: bound(start)?.map(|x| x.0),
+            end: bound(end)?.map(|x| x.1),
+        })
+    }
+}
+
+fn parse_status(s: &str) -> Result<PhotoRecordStatus> {
+    Ok(match s {
+        "committed" => Committed,
+        "missing" => CommittedButMissing,
+        "modified" => CommittedButModified,
+        "untracked" | "uncommitted" => Uncommitted,
+        _ => bail!(
+            "unknown status {}, expect committed, missing, modified or untracked",
+            s
+        ),
+    })
+}
+
+pub fn parse_rating(s: &str) -> Result<u8> {
+    match s.parse() {
+        Ok(rating) if rating <= MAX_RATING => Ok(rating),
+        _ => bail!("invalid rating {}, expect 0 to {}", s, MAX_RATING),
+    }
+}
+
+// "100", "100..400", "..400" or "800..", both ends inclusive
+fn parse_range<T>(s: &str, min: T, max: T) -> Result<RangeInclusive<T>>
+where
+    T: FromStr + Copy,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let bound = |x: &str, default| match x.is_empty() {
+        true => Ok(default),
+        false => x
+            .parse()
+            .with_context(|| format!("cannot parse range {}", s)),
+    };
+    match s.split_once("..") {
+        Some((start, end)) => Ok(bound(start, min)?..=bound(end, max)?),
+        None => {
+            let x = bound(s, min)?;
+            Ok(x..=x)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+    Word(String),
+}
+
+fn tokenize(s: &str) -> Vec<Token> {
+    use Token::*;
+    let mut tokens = vec![];
+    let mut word = String::new();
+    let mut quoted = false;
+    let flush = |word: &mut String, tokens: &mut Vec<Token>| {
+        if word.is_empty() {
+            return;
+        }
+        tokens.push(match word.as_str() {
+            "and" => And,
+            "or" => Or,
+            "not" => Not,
+            _ => Word(word.clone()),
+        });
+        word.clear();
+    };
+    for c in s.chars() {
+        let token = match c {
+            '"' => {
+                quoted = !quoted;
+                continue;
+            }
+            c if quoted => {
+                word.push(c);
+                continue;
+            }
+            '&' => Some(And),
+            '|' => Some(Or),
+            '!' => Some(Not),
+            '(' => Some(LParen),
+            ')' => Some(RParen),
+            c if c.is_whitespace() => None,
+            c => {
+                word.push(c);
+                continue;
+            }
+        };
+        flush(&mut word, &mut tokens);
+        tokens.extend(token);
+    }
+    flush(&mut word, &mut tokens);
+    tokens
+}
+
+struct Parser {
+    tokens: std::iter::Peekable<std::vec::IntoIter<Token>>,
+}
+
+impl Parser {
+    fn eat(&mut self, token: Token) -> bool {
+        self.tokens.next_if_eq(&token).is_some()
+    }
+    fn parse_or(&mut self) -> Result<PhotoFilter> {
+        let mut lhs = self.parse_and()?;
+        while self.eat(Token::Or) {
+            lhs = PhotoFilter::Or(lhs.into(), self.parse_and()?.into());
+        }
+        Ok(lhs)
+    }
+    fn parse_and(&mut self) -> Result<PhotoFilter> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            match self.tokens.peek() {
+                Some(Token::And) => {
+                    self.tokens.next();
+                }
+                Some(Token::Not | Token::LParen | Token::Word(_)) => {}
+                _ => return Ok(lhs),
+            }
+            lhs = PhotoFilter::And(lhs.into(), self.parse_unary()?.into());
+        }
+    }
+    fn parse_unary(&mut self) -> Result<PhotoFilter> {
+        match self.tokens.next() {
+            Some(Token::Not) => Ok(PhotoFilter::Not(self.parse_unary()?.into())),
+            Some(Token::LParen) => {
+                let inner = self.parse_or()?;
+                if !self.eat(Token::RParen) {
+                    bail!("unbalanced parentheses");
+                }
+                Ok(inner)
+            }
+            Some(Token::Word(word)) => PhotoFilter::parse_term(&word),
+            Some(token) => bail!("unexpected {:?}", token),
+            None => bail!("unexpected end of query"),
+        }
+    }
+}
+
+impl PhotoFilter {
+    fn parse_term(word: &str) -> Result<Self> {
+        use PhotoFilter::*;
+        match word {
+            "selected" => return Ok(Selected),
+            "favorite" => return Ok(Favorite),
+            _ => {}
+        }
+        if let Some((key, value)) = word.split_once(':') {
+            return Ok(match key {
+                "pid" => Pids(match PID::from_str(value) {
+                    Ok(pid) => PidRange::single(pid),
+                    Err(_) => value.parse()?,
+                }),
+                "mount" => Mount(value.parse()?),
+                "status" => Status(parse_status(value)?),
+                "date" => Date(value.parse()?),
+                "name" => Name(glob::Pattern::new(value)?),
+                "rating" => {
+                    let (value, at_least) = match value.strip_suffix('+') {
+                        Some(value) => (value, true),
+                        None => (value, false),
+                    };
+                    let rating = parse_rating(value)?;
+                    Rating(rating, if at_least { MAX_RATING } else { rating })
+                }
+                "tag" => Tag(value.to_owned()),
+                "album" => Album(value.to_owned()),
+                "person" => Person(value.to_owned()),
+                "place" => {
+                    // or `!place:x` would select every photo
+                    geocoder().context("place: cannot be used")?;
+                    Place(value.to_owned())
+                }
+                "camera" => Camera(value.to_lowercase()),
+                "lens" => Lens(value.to_lowercase()),
+                "iso" => Iso(parse_range(value, 0, u32::MAX)?),
+                "focal" => {
+                    let mm = parse_range(value, 0., f64::INFINITY)?;
+                    let tenths = |x: f64| (x * 10.).round() as u32;
+                    FocalLength(tenths(*mm.start())..=tenths(*mm.end()))
+                }
+                _ => bail!("unknown query key {}", key),
+            });
+        }
+        if let Ok(pid) = PID::from_str(word) {
+            return Ok(Pids(PidRange::single(pid)));
+        }
+        if let Ok(range) = PidRange::from_str(word) {
+            return Ok(Pids(range));
+        }
+        // a bare MPID, as `butler list <mpid>` used to take
+        if let Ok(uuid) = Uuid::parse_str(word) {
+            return Ok(Mount(MountSelector::Mpid(uuid)));
+        }
+        if word.contains(&['*', '?', '['][..]) {
+            return Ok(Name(glob::Pattern::new(word)?));
+        }
+        Ok(Location(Path::new(word).canonicalize()?))
+    }
+
+    // whether any term satisfies f
+    fn mentions(&self, f: &dyn Fn(&PhotoFilter) -> bool) -> bool {
+        use PhotoFilter::*;
+        match self {
+            Not(x) => x.mentions(f),
+            And(x, y) | Or(x, y) => x.mentions(f) || y.mentions(f),
+            x => f(x),
+        }
+    }
+
+    fn eval(&self, table: &PhotoTable, ctx: &EvalContext<'_>) -> Result<BTreeSet<PID>> {
+        use PhotoFilter::*;
+        let scan = |f: &dyn Fn(&PhotoRecord) -> bool| -> BTreeSet<PID> {
+            table
+                .pid2rec
+                .values()
+                .filter(|rec| f(rec))
+                .map(|rec| rec.pid)
+                .collect()
+        };
+        Ok(match self {
+            Pids(range) => match range.bounds() {
+                Some(bounds) => table.pid2rec.range(bounds).map(|(pid, _)| *pid).collect(),
+                None => BTreeSet::new(),
+            },
+            Location(path) => {
+                let loc = Arc::new(FileLocation::from_path(path, ctx.mounts)?);
+                table.index.loc2pid.get(&loc).cloned().into_iter().collect()
+            }
+            // photos of a removed mount point can still be listed by its MPID
+            Mount(MountSelector::Mpid(mpid)) => ctx.select(table, IndexedFilter::Mount(*mpid))?,
+            Mount(sel) => {
+                let mpid = ctx.mounts.resolve(sel)?.uuid;
+                ctx.select(table, IndexedFilter::Mount(mpid))?
+            }
+            Status(status) => ctx.select(table, IndexedFilter::Status(*status))?,
+            Selected => ctx.select(table, IndexedFilter::Selected)?,
+            Favorite => table.index.favorites().collect(),
+            Rating(least, most) => table.index.rated_within(*least..=*most).collect(),
+            Date(range) => {
+                // a day around the range covers any UTC offset, unbounded
+                // past the first or the last day representable
+                let utc = |d: NaiveDate| DateTime::<Utc>::from_utc(d.and_hms(0, 0, 0), Utc);
+                let start = range.start.and_then(|d| d.pred_opt()).map(utc);
+                let end = range.end.and_then(|d| d.succ_opt()).map(utc);
+                let taken = IndexedFilter::Taken(
+                    start.map_or(Unbounded, Included),
+                    end.map_or(Unbounded, Excluded),
+                );
+                ctx.select(table, taken)?
+                    .into_iter()
+                    .filter(|pid| {
+                        let rec = &table.pid2rec[pid];
+                        range.contains(local_date(rec, &ctx.tzs).unwrap())
+                    })
+                    .collect()
+            }
+            Name(pattern) => scan(&|rec| pattern.matches_path(&rec.location.filename)),
+            Camera(part) => scan(&|rec| {
+                rec.metadata
+                    .camera
+                    .device()
+                    .map_or(false, |x| x.to_lowercase().contains(part))
+            }),
+            Lens(part) => scan(&|rec| {
+                rec.metadata
+                    .camera
+                    .lens
+                    .as_ref()
+                    .map_or(false, |x| x.to_lowercase().contains(part))
+            }),
+            Iso(range) => scan(&|rec| {
+                rec.metadata
+                    .camera
+                    .iso
+                    .map_or(false, |x| range.contains(&x))
+            }),
+            FocalLength(range) => scan(&|rec| {
+                rec.metadata
+                    .camera
+                    .focal_length
+                    .map_or(false, |x| range.contains(&x))
+            }),
+            Place(name) => {
+                let geocoder = geocoder()?;
+                scan(&|rec| {
+                    rec.metadata
+                        .gps
+                        .and_then(|gps| geocoder.nearest(&gps))
+                        .map_or(false, |place| place.matches(name))
+                })
+            }
+            Tag(name) => table.index.labelled(Label::Tag, name).collect(),
+            Album(name) => table.index.labelled(Label::Album, name).collect(),
+            // faces of removed photos linger until the next `butler faces`
+            Person(name) => ctx
+                .faces
+                .photos_of(name)
+                .into_iter()
+                .filter(|pid| table.pid2rec.contains_key(pid))
+                .collect(),
+            Not(x) => {
+                let x = x.eval(table, ctx)?;
+                scan(&|rec| !x.contains(&rec.pid))
+            }
+            And(x, y) => {
+                let x = x.eval(table, ctx)?;
+                if x.is_empty() {
+                    return Ok(x);
+                }
+                &x & &y.eval(table, ctx)?
+            }
+            Or(x, y) => &x.eval(table, ctx)? | &y.eval(table, ctx)?,
+        })
+    }
+}
+
+// what evaluating filters takes besides the table, loaded once per select
+// and only if some term needs it
+struct EvalContext<'a> {
+    mounts: &'a MountPointTable,
+    storage: &'a dyn Storage,
+    tzs: HashMap<Uuid, FixedOffset>,
+    faces: FaceStore,
+}
+
+impl<'a> EvalContext<'a> {
+    fn select(&self, table: &PhotoTable, filter: IndexedFilter) -> Result<BTreeSet<PID>> {
+        self.storage.select(table, &filter)
+    }
+}
+
+// the terms the storages keep an index for
+#[derive(Clone, Debug)]
+pub enum IndexedFilter {
+    Status(PhotoRecordStatus),
+    Selected,
+    Mount(Uuid),
+    // by the effective EXIF time
+    Taken(Bound<DateTime<Utc>>, Bound<DateTime<Utc>>),
+}
+
+impl IndexedFilter {
+    pub fn matches(&self, rec: &PhotoRecord) -> bool {
+        use IndexedFilter::*;
+        match self {
+            Status(status) => rec.status == *status,
+            Selected => rec.selected,
+            Mount(mpid) => rec.location.mpid == *mpid,
+            Taken(start, end) => rec.etime().map_or(false, |t| (*start, *end).contains(&t)),
+        }
+    }
+    // through the index of the table in memory
+    pub(in crate::db) fn eval_in(&self, table: &PhotoTable) -> BTreeSet<PID> {
+        use IndexedFilter::*;
+        match self {
+            Status(status) => table.index.with_status(*status).collect(),
+            Selected => table.index.selected().collect(),
+            Mount(mpid) => table.index.on_mount(mpid).collect(),
+            Taken(start, end) => table.index.taken_within((*start, *end)).collect(),
+        }
+    }
+}
+
+impl FromStr for PhotoFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s).into_iter().peekable(),
+        };
+        let filter = parser
+            .parse_or()
+            .with_context(|| format!("cannot parse query {:?}", s))?;
+        if let Some(token) = parser.tokens.next() {
+            bail!("cannot parse query {:?}: unexpected {:?}", s, token);
+        }
+        Ok(filter)
+    }
+}
+
+fn mount_timezones(mounts: &MountPointTable) -> HashMap<Uuid, FixedOffset> {
+    mounts
+        .records()
+        .map(|mp| (mp.uuid, mp.utc_offset.fixed()))
+        .collect()
+}
+
+// the day a photo was taken, in the timezone of its mount point
+fn local_date(rec: &PhotoRecord, tzs: &HashMap<Uuid, FixedOffset>) -> Option<NaiveDate> {
+    let tz = tzs
+        .get(&rec.location.mpid)
+        .cloned()
+        .unwrap_or_else(|| UtcOffset::default().fixed());
+    rec.etime()
+        .map(|t| t.with_timezone(&tz).date().naive_local())
+}
+
+impl<'a> TableAccess<'a, PhotoTable> {
+    // PIDs matching any of the filters
+    pub fn select<'f, I>(&self, filters: I) -> Result<BTreeSet<PID>>
+    where
+        I: IntoIterator<Item = &'f PhotoFilter>,
+    {
+        let filters = filters.into_iter().collect::<Vec<_>>();
+        let uses = |f: &dyn Fn(&PhotoFilter) -> bool| filters.iter().any(|x| x.mentions(f));
+        let ctx = EvalContext {
+            mounts: self.context,
+            storage: self.storage,
+            tzs: match uses(&|x| matches!(x, PhotoFilter::Date(_))) {
+                true => mount_timezones(self.context),
+                false => HashMap::new(),
+            },
+            faces: match uses(&|x| matches!(x, PhotoFilter::Person(_))) {
+                true => FaceStore::load()?,
+                false => FaceStore::default(),
+            },
+        };
+        let mut pids = BTreeSet::new();
+        for filter in filters {
+            pids.extend(filter.eval(self.table, &ctx)?);
+        }
+        Ok(pids)
+    }
+
+    // photos with a time grouped by the local day they were taken
+    pub fn by_day(&self) -> BTreeMap<NaiveDate, Vec<&'a PhotoRecord>> {
+        let tzs = mount_timezones(self.context);
+        let mut days = BTreeMap::<_, Vec<_>>::new();
+        for rec in self
+            .table
+            .records_between(chrono::MIN_DATETIME, chrono::MAX_DATETIME)
+        {
+            days.entry(local_date(rec, &tzs).unwrap())
+                .or_default()
+                .push(rec);
+        }
+        days
+    }
+}
+
+impl<'a> TableAccessMut<'a, PhotoTable> {
+    pub fn select<'f, I>(&self, filters: I) -> Result<BTreeSet<PID>>
+    where
+        I: IntoIterator<Item = &'f PhotoFilter>,
+    {
+        self.access().select(filters)
+    }
+    pub fn by_day(&self) -> BTreeMap<NaiveDate, Vec<&PhotoRecord>> {
+        self.access().by_day()
+    }
+}
+
+impl PhotoTable {
+    pub fn records_of<'a, I>(&'a self, pids: I) -> impl Iterator<Item = &'a PhotoRecord> + 'a
+    where
+        I: IntoIterator<Item = PID> + 'a,
+    {
+        pids.into_iter()
+            .filter_map(move |pid| self.pid2rec.get(&pid))
+    }
+
+    // names of tags or albums with their numbers of photos
+    pub fn labels(&self, label: Label) -> BTreeMap<&str, usize> {
+        self.index.label_counts(label)
+    }
+
+    // photos taken in [start, end), in order of time
+    pub fn records_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> impl Iterator<Item = &PhotoRecord> {
+        self.index
+            .taken_within(start..end)
+            .map(move |pid| &self.pid2rec[&pid])
+    }
+}
+
+#[test]
+fn test_parse_filter() -> Result<()> {
+    use PhotoFilter::*;
+    let filter = PhotoFilter::from_str("1..3 status:missing | !(selected or name:*.png)")?;
+    let (lhs, rhs) = match filter {
+        Or(lhs, rhs) => (lhs, rhs),
+        x => bail!("unexpected {:?}", x),
+    };
+    assert!(matches!(
+        *lhs,
+        And(ref x, ref y) if matches!(**x, Pids(PidRange { start: Some(1), end: Some(2) }))
+            && matches!(**y, Status(CommittedButMissing))
+    ));
+    assert!(matches!(*rhs, Not(ref x) if matches!(**x, Or(_, _))));
+    let filter = PhotoFilter::from_str(r#"album:"Travel 2021"&!tag:a"#)?;
+    assert!(matches!(
+        filter,
+        And(ref x, ref y) if matches!(**x, Album(ref name) if name == "Travel 2021")
+            && matches!(**y, Not(_))
+    ));
+
+    let date = |s| DateRange::from_str(s).unwrap();
+    let ymd = |y, m, d| Some(NaiveDate::from_ymd(y, m, d));
+    assert_eq!(date("2021").start, ymd(2021, 1, 1));
+    assert_eq!(date("2021").end, ymd(2022, 1, 1));
+    assert_eq!(date("2021-12").end, ymd(2022, 1, 1));
+    assert_eq!(date("2021-02-28").end, ymd(2021, 3, 1));
+    assert_eq!(date("2021-05..2021-07").end, ymd(2021, 8, 1));
+    assert_eq!(date("..2021-07").start, None);
+    assert!(DateRange::from_str("4294967295").is_err());
+    assert!(DateRange::from_str("2147483648").is_err());
+    assert!(DateRange::from_str("2021-4294967295").is_err());
+    assert!(DateRange::from_str("262143-12-31").is_err());
+    assert!(PhotoFilter::from_str("(selected").is_err());
+    assert!(PhotoFilter::from_str("selected )").is_err());
+    assert!(PhotoFilter::from_str("foo:bar").is_err());
+    assert!(matches!(PhotoFilter::from_str("iso:..200")?, Iso(x) if x == (0..=200)));
+    assert!(matches!(PhotoFilter::from_str("focal:35")?, FocalLength(x) if x == (350..=350)));
+    assert!(PhotoFilter::from_str("iso:abc").is_err());
+    assert!(matches!(PhotoFilter::from_str("rating:4+")?, Rating(4, 5)));
+    assert!(PhotoFilter::from_str("rating:6").is_err());
+    Ok(())
+}
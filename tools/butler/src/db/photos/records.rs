@@ -10,35 +10,48 @@ pub struct LocalPhoto {
     pub location: Arc<FileLocation>,
     pub file_hash: Option<FileHash>,
     pub metadata: PhotoMetadata,
+    pub fingerprint: Option<PhotoFingerprint>,
     mmap: Option<filebuffer::FileBuffer>,
 }
 
 impl LocalPhoto {
-    pub fn new(location: FileLocation) -> Result<Self> {
-        let metadata = PhotoMetadata::from_path(location.filepath())?;
+    pub fn new(location: Arc<FileLocation>, default_tz: FixedOffset) -> Result<Self> {
+        let metadata = PhotoMetadata::from_path(location.filepath()?, default_tz)?;
         Ok(Self {
-            location: location.into(),
+            location,
             metadata,
             file_hash: None,
+            fingerprint: None,
             mmap: None,
         })
     }
-    fn filepath(&self) -> &Path {
-        self.location.filepath()
-    }
     pub fn prefetch(&mut self) -> Result<()> {
-        let mmap = filebuffer::FileBuffer::open(self.filepath())?;
+        let mmap = filebuffer::FileBuffer::open(self.location.filepath()?)?;
         if mmap.len() > 0 {
             mmap.prefetch(0, mmap.len());
         }
         self.mmap.replace(mmap);
         Ok(())
     }
+    pub fn fill_fingerprint(&mut self) -> Result<()> {
+        if self.fingerprint.is_some() {
+            return Ok(());
+        }
+        let mmap = self.mmap.as_ref().unwrap();
+        if mmap.len() == 0 {
+            return Ok(());
+        }
+        match PhotoFingerprint::from_buffer(mmap.as_ref()) {
+            Ok(fp) => self.fingerprint = Some(fp),
+            Err(e) => warn!("Cannot fingerprint {}: {}", self.location, e),
+        }
+        Ok(())
+    }
     pub fn fill_file_hash(&mut self) -> Result<()> {
         if self.file_hash.is_some() {
             return Ok(());
         }
-        info!("Hashing {}...", self.filepath().display());
+        info!("Hashing {}...", self.location);
         let mmap = self.mmap.take().unwrap();
         self.file_hash = if mmap.len() == 0 {
             Some(0)
@@ -58,8 +71,15 @@ pub struct PhotoRecord {
     pub location: Arc<FileLocation>,
     pub file_hash: FileHash,
     pub metadata: PhotoMetadata,
+    pub fingerprint: Option<PhotoFingerprint>,
 
     // omoyde related
+    pub etime_shift: i64, // seconds added to EXIF time, for cameras with a wrong clock
+    pub overrides: PhotoOverrides,
+    pub tags: BTreeSet<String>,
+    pub albums: BTreeSet<String>,
+    pub rating: u8, // 0 for unrated, up to MAX_RATING
+    pub favorite: bool,
     pub selected: bool,
     pub status: PhotoRecordStatus,
     pub commit_time: Option<DateTime<Utc>>,
@@ -80,9 +100,61 @@ impl PhotoRecord {
             location: file.location.into(),
             file_hash: file.file_hash.unwrap(),
             metadata: file.metadata,
+            fingerprint: file.fingerprint,
+            etime_shift: 0,
+            overrides: Default::default(),
+            tags: BTreeSet::new(),
+            albums: BTreeSet::new(),
+            rating: 0,
+            favorite: false,
             selected: false,
             status: Uncommitted,
             commit_time: None,
         }
     }
+    pub fn etime(&self) -> Option<DateTime<Utc>> {
+        self.overrides
+            .etime
+            .or_else(|| self.shifted_etime(self.etime_shift))
+    }
+    // None as well if shifted out of the range of DateTime
+    pub fn shifted_etime(&self, etime_shift: i64) -> Option<DateTime<Utc>> {
+        let t = self.metadata.etime?;
+        t.checked_add_signed(crate::util::time::seconds(etime_shift)?)
+    }
+    pub fn effective_metadata(&self) -> PhotoMetadata {
+        let mut metadata = self.metadata.clone();
+        self.overrides.apply_to(&mut metadata);
+        metadata.etime = self.etime();
+        metadata
+    }
+}
+
+// names of tags and albums, kept to one line and free of the commas that
+// separate them in CSV exports and of the quotes of queries
+pub fn validate_label(name: &str) -> Result<()> {
+    if name.trim().is_empty() || name.trim() != name {
+        bail!("{:?} is empty or padded with whitespace", name);
+    }
+    if name.contains(&[',', '"', '\n', '\r'][..]) {
+        bail!("{:?} contains a comma, a quote or a line break", name);
+    }
+    if name.len() > 255 {
+        bail!("{:?} is longer than 255 bytes", name);
+    }
+    Ok(())
+}
+
+// shown by the web app, stored in u16-prefixed UTF-8 in captions.bin
+const MAX_CAPTION_BYTES: usize = u16::MAX as usize;
+
+// as stored: trimmed and non-empty, an empty caption is removed instead
+pub fn validate_caption(text: &str) -> Result<()> {
+    if text.trim().is_empty() || text.trim() != text {
+        bail!("caption {:?} is empty or padded with whitespace", text);
+    }
+    if text.len() > MAX_CAPTION_BYTES {
+        bail!("caption longer than {} bytes", MAX_CAPTION_BYTES);
+    }
+    Ok(())
 }
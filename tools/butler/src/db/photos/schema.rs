@@ -0,0 +1,284 @@
+use super::table::PhotoTable;
+use crate::prelude::*;
+use crate::util::serde::{migrate_with, Migration, Versioned};
+use rusqlite::types::Value;
+
+impl Versioned for PhotoTable {
+    const MAGIC: &'static [u8; 4] = b"BTPT";
+    const VERSION: u32 = 1;
+    const MIGRATIONS: &'static [Migration] = &[Migration {
+        from: 0,
+        run: v0_to_v1,
+    }];
+}
+
+// named as in query filters
+pub(in crate::db) fn sql_status(status: PhotoRecordStatus) -> &'static str {
+    match status {
+        Committed => "committed",
+        CommittedButMissing => "missing",
+        CommittedButModified => "modified",
+        Uncommitted => "untracked",
+    }
+}
+
+impl StoredTable for PhotoTable {
+    const NAME: &'static str = "photos";
+    const KEY_TYPE: &'static str = "INTEGER";
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("mpid", "TEXT"),
+        ("filename", "TEXT"),
+        ("status", "TEXT"),
+        ("selected", "INTEGER"),
+        ("etime", "INTEGER"),
+        ("rating", "INTEGER"),
+        ("favorite", "INTEGER"),
+        ("latitude", "REAL"),
+        ("longitude", "REAL"),
+        ("camera", "TEXT"),
+        ("lens", "TEXT"),
+    ];
+
+    fn sql_key(pid: &PID) -> Value {
+        Value::Integer(*pid as i64)
+    }
+    fn sql_columns(rec: &PhotoRecord) -> Vec<Value> {
+        let gps = rec.metadata.gps;
+        let camera = &rec.metadata.camera;
+        vec![
+            Value::Text(rec.location.mpid.to_string()),
+            Value::Text(rec.location.filename.to_string_lossy().into_owned()),
+            Value::Text(sql_status(rec.status).to_owned()),
+            Value::Integer(rec.selected as i64),
+            // effective, with shift and override applied
+            rec.etime()
+                .map_or(Value::Null, |t| Value::Integer(t.timestamp())),
+            Value::Integer(rec.rating as i64),
+            Value::Integer(rec.favorite as i64),
+            gps.map_or(Value::Null, |gps| Value::Real(gps.latitude())),
+            gps.map_or(Value::Null, |gps| Value::Real(gps.longitude())),
+            camera.device().map_or(Value::Null, Value::Text),
+            camera.lens.clone().map_or(Value::Null, Value::Text),
+        ]
+    }
+}
+
+// Frozen snapshots of past schemas. Never change them, when a field is
+// added to the table, bump VERSION, snapshot the new schema and add a
+// migration from the previous one.
+
+// fingerprint, shift, overrides, labels, rating, favorite, GPS and camera
+// added since
+pub(super) mod v1 {
+    use crate::prelude::*;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct PhotoTable {
+        pub counter: u32,
+        pub pid2rec: BTreeMap<u32, PhotoRecord>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct PhotoRecord {
+        pub pid: u32,
+        pub location: FileLocation,
+        pub file_hash: u64,
+        pub metadata: PhotoMetadata,
+        pub fingerprint: Option<PhotoFingerprint>,
+        pub etime_shift: i64,
+        pub overrides: PhotoOverrides,
+        pub tags: BTreeSet<String>,
+        pub albums: BTreeSet<String>,
+        pub rating: u8,
+        pub favorite: bool,
+        pub selected: bool,
+        pub status: PhotoRecordStatus,
+        pub commit_time: Option<DateTime<Utc>>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct FileLocation {
+        pub mpid: Uuid,
+        pub filename: PathBuf,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct PhotoMetadata {
+        pub ctime: DateTime<Utc>,
+        pub mtime: DateTime<Utc>,
+        pub file_length: u64,
+        pub etime: Option<DateTime<Utc>>,
+        pub width: u32,
+        pub height: u32,
+        pub orientation: PhotoOrientation,
+        pub gps: Option<GeoPosition>,
+        pub camera: CameraInfo,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum PhotoOrientation {
+        D0,
+        D90,
+        D180,
+        D270,
+        MirroredD0,
+        MirroredD90,
+        MirroredD180,
+        MirroredD270,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct GeoPosition {
+        pub latitude: i32,
+        pub longitude: i32,
+        pub altitude: Option<i32>,
+    }
+
+    #[derive(Serialize, Deserialize, Default)]
+    pub struct CameraInfo {
+        pub make: Option<String>,
+        pub model: Option<String>,
+        pub lens: Option<String>,
+        pub focal_length: Option<u32>,
+        pub iso: Option<u32>,
+        pub exposure_time: Option<(u32, u32)>,
+        pub f_number: Option<u32>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct PhotoFingerprint {
+        pub phash: u64,
+        pub sharpness: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Default)]
+    pub struct PhotoOverrides {
+        pub etime: Option<DateTime<Utc>>,
+        pub orientation: Option<PhotoOrientation>,
+        pub caption: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum PhotoRecordStatus {
+        Committed,
+        CommittedButMissing,
+        CommittedButModified,
+        Uncommitted,
+    }
+}
+
+// the schema before versioning, orientations then had no mirrored variants
+pub(super) mod v0 {
+    use super::v1::{FileLocation, PhotoOrientation, PhotoRecordStatus};
+    use crate::prelude::*;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct PhotoTable {
+        pub counter: u32,
+        pub pid2rec: BTreeMap<u32, PhotoRecord>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct PhotoRecord {
+        pub pid: u32,
+        pub location: FileLocation,
+        pub file_hash: u64,
+        pub metadata: PhotoMetadata,
+        pub selected: bool,
+        pub status: PhotoRecordStatus,
+        pub commit_time: Option<DateTime<Utc>>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct PhotoMetadata {
+        pub ctime: DateTime<Utc>,
+        pub mtime: DateTime<Utc>,
+        pub file_length: u64,
+        pub etime: Option<DateTime<Utc>>,
+        pub width: u32,
+        pub height: u32,
+        pub orientation: PhotoOrientation,
+    }
+}
+
+// GPS and camera are read by the next `butler index --full`
+fn v0_to_v1(payload: &[u8]) -> Result<Vec<u8>> {
+    migrate_with(payload, |table: v0::PhotoTable| v1::PhotoTable {
+        counter: table.counter,
+        pid2rec: table
+            .pid2rec
+            .into_iter()
+            .map(|(pid, rec)| {
+                let metadata = rec.metadata;
+                let rec = v1::PhotoRecord {
+                    pid: rec.pid,
+                    location: rec.location,
+                    file_hash: rec.file_hash,
+                    metadata: v1::PhotoMetadata {
+                        ctime: metadata.ctime,
+                        mtime: metadata.mtime,
+                        file_length: metadata.file_length,
+                        etime: metadata.etime,
+                        width: metadata.width,
+                        height: metadata.height,
+                        orientation: metadata.orientation,
+                        gps: None,
+                        camera: Default::default(),
+                    },
+                    fingerprint: None,
+                    etime_shift: 0,
+                    overrides: Default::default(),
+                    tags: BTreeSet::new(),
+                    albums: BTreeSet::new(),
+                    rating: 0,
+                    favorite: false,
+                    selected: rec.selected,
+                    status: rec.status,
+                    commit_time: rec.commit_time,
+                };
+                (pid, rec)
+            })
+            .collect(),
+    })
+}
+
+#[test]
+fn test_migrate_from_v0() -> Result<()> {
+    let time = Utc.timestamp(1600000000, 0);
+    let rec = v0::PhotoRecord {
+        pid: 3,
+        location: v1::FileLocation {
+            mpid: Uuid::new_v4(),
+            filename: "IMG_0001.jpg".into(),
+        },
+        file_hash: 42,
+        metadata: v0::PhotoMetadata {
+            ctime: time,
+            mtime: time,
+            file_length: 1024,
+            etime: Some(time),
+            width: 4000,
+            height: 3000,
+            orientation: v1::PhotoOrientation::D90,
+        },
+        selected: true,
+        status: v1::PhotoRecordStatus::Committed,
+        commit_time: Some(time),
+    };
+    let v0 = v0::PhotoTable {
+        counter: 4,
+        pid2rec: [(3, rec)].into_iter().collect(),
+    };
+    let table: PhotoTable = bincode::deserialize(&v0_to_v1(&bincode::serialize(&v0)?)?)?;
+    assert_eq!(table.counter.get(), 4);
+    let rec = &table.pid2rec[&3];
+    assert_eq!(&*rec.location.filename, Path::new("IMG_0001.jpg"));
+    assert_eq!(rec.metadata.orientation, PhotoOrientation::D90);
+    assert_eq!(rec.etime(), Some(time));
+    assert!(rec.selected && rec.fingerprint.is_none() && rec.tags.is_empty());
+    assert!(rec.rating == 0 && !rec.favorite);
+    assert!(rec.metadata.gps.is_none());
+    assert_eq!(rec.metadata.camera, CameraInfo::default());
+    assert_eq!(rec.status, Committed);
+    Ok(())
+}
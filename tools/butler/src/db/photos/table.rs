@@ -6,7 +6,7 @@ use crate::util::sync::*;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PhotoTable {
     #[serde(skip)]
-    modified: AtomicFlag,
+    pub(super) changes: Changes<PID>,
     pub(super) counter: AtomicCounter,
     pub(super) pid2rec: BTreeMap<u32, PhotoRecord>,
     #[serde(skip)]
@@ -17,38 +17,51 @@ impl Table for PhotoTable {
     type Record = PhotoRecord;
     type PrimaryKey = PID;
     type Index = PhotoTableIndex;
+    type Context = MountPointTable;
 
     fn insert(&mut self, mut rec: PhotoRecord) -> &mut PhotoRecord {
         let pid = self.counter.get_and_incr();
         rec.pid = pid;
         self.index.insert(&rec);
+        self.changes.head = true;
+        self.changes.touch(pid);
         self.pid2rec.entry(pid).or_insert(rec)
     }
 
-    fn index_mut(&mut self) -> &mut PhotoTableIndex {
-        &mut self.index
+    fn treemap(&self) -> &BTreeMap<PID, PhotoRecord> {
+        &self.pid2rec
     }
 
-    fn treemap_mut(&mut self) -> &mut BTreeMap<PID, PhotoRecord> {
-        &mut self.pid2rec
+    fn changes(&self) -> &Changes<PID> {
+        &self.changes
     }
 
-    fn modified_flag(&self) -> &AtomicFlag {
-        &self.modified
+    fn split_mut(
+        &mut self,
+    ) -> (
+        &mut BTreeMap<PID, PhotoRecord>,
+        &mut PhotoTableIndex,
+        &mut Changes<PID>,
+    ) {
+        (&mut self.pid2rec, &mut self.index, &mut self.changes)
     }
 }
 
 impl PhotoTable {
-    pub fn new() -> Self {
+    pub(in crate::db) fn new() -> Self {
         Self {
-            modified: AtomicFlag::new(),
+            changes: Default::default(),
             counter: AtomicCounter::new(0),
             pid2rec: BTreeMap::new(),
             index: PhotoTableIndex::default(),
         }
     }
-}
-
-lazy_static! {
-    pub static ref PHOTO_TABLE: Mutex<PhotoTable> = Mutex::new(PhotoTable::new());
+    // where the files are, on the mount points as loaded
+    pub(in crate::db) fn locate(&self, mounts: &MountPointTable) {
+        for rec in self.pid2rec.values() {
+            if let Ok(mp) = mounts.resolve(&rec.location.mpid.into()) {
+                rec.location.locate(&mp.path);
+            }
+        }
+    }
 }
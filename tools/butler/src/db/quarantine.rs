@@ -0,0 +1,56 @@
+use crate::db::journal;
+use crate::prelude::*;
+use crate::util::serde::{decode_versioned, save_versioned, Migration, Versioned};
+
+// A file that `index` failed to read, kept aside so that the rest of
+// the photos can still be indexed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuarantinedFile {
+    pub location: Arc<FileLocation>,
+    pub pid: Option<PID>,
+    pub error: String,
+    pub time: DateTime<Utc>, // when first quarantined
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Quarantine {
+    pub files: Vec<QuarantinedFile>,
+}
+
+impl Versioned for Quarantine {
+    const MAGIC: &'static [u8; 4] = b"BTQR";
+    const VERSION: u32 = 1;
+    const MIGRATIONS: &'static [Migration] = &[];
+}
+
+impl Quarantine {
+    pub fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let (quarantine, _) = decode_versioned(path, &fs::read(path)?)?;
+        Ok(quarantine)
+    }
+    // keeps when files still unreadable were first quarantined, returns
+    // how many of them were already in `previous`
+    pub fn carry_over(&mut self, previous: Quarantine) -> usize {
+        let since = previous
+            .files
+            .into_iter()
+            .map(|file| (file.location, file.time))
+            .collect::<HashMap<_, _>>();
+        let mut n = 0;
+        for file in self.files.iter_mut() {
+            if let Some(time) = since.get(&file.location) {
+                file.time = *time;
+                n += 1;
+            }
+        }
+        n
+    }
+    // only staged, the file is replaced by journal::commit()
+    pub fn finalize<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        journal::stage_file(path.as_ref(), |staged| save_versioned(self, staged))
+    }
+}
@@ -0,0 +1,52 @@
+use super::*;
+use crate::db::journal;
+use crate::util::serde::TableIO;
+
+// one bincode file per table, rewritten whole when it changed
+pub struct BincodeStorage {
+    dir: PathBuf,
+}
+
+impl BincodeStorage {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().into(),
+        }
+    }
+}
+
+impl<T> TableStorage<T> for BincodeStorage
+where
+    T: StoredTable,
+{
+    fn load(&self, table: &mut T) -> Result<()> {
+        table.load_from_path(self.dir.join(T::NAME))
+    }
+    fn stage(&self, table: &mut T) -> Result<()> {
+        let path = self.dir.join(T::NAME);
+        if table.changes().is_empty() && path.exists() {
+            debug!("Table unmodified, will not save to {}", path.display());
+            return Ok(());
+        }
+        journal::stage_file(&path, |staged| table.save_to_path(staged))
+    }
+}
+
+impl Storage for BincodeStorage {
+    fn select(&self, photos: &PhotoTable, filter: &IndexedFilter) -> Result<BTreeSet<PID>> {
+        Ok(filter.eval_in(photos))
+    }
+    fn commit(&self, journal: &[(PathBuf, PathBuf)]) -> Result<()> {
+        if journal.is_empty() {
+            return Ok(());
+        }
+        journal::write_file_journal(journal)
+    }
+    fn discard(&self) {}
+    fn journals(&self) -> Result<Vec<Vec<(PathBuf, PathBuf)>>> {
+        Ok(journal::read_file_journal()?.into_iter().collect())
+    }
+    fn clear_journals(&self) -> Result<()> {
+        journal::remove_file_journal()
+    }
+}
@@ -0,0 +1,41 @@
+mod files;
+mod sqlite;
+
+pub use files::BincodeStorage;
+pub use sqlite::SqliteStorage;
+
+use crate::prelude::*;
+use crate::util::serde::Versioned;
+use rusqlite::types::Value;
+
+// A table as kept by the storages, as a bincode file named NAME in the
+// database directory, or as the SQLite table NAME with a row per record.
+pub trait StoredTable: Table + Versioned + Serialize + serde::de::DeserializeOwned {
+    const NAME: &'static str;
+    const KEY_TYPE: &'static str;
+    // indexed columns besides the key, as (name, type)
+    const COLUMNS: &'static [(&'static str, &'static str)];
+
+    fn sql_key(key: &Self::PrimaryKey) -> Value;
+    fn sql_columns(rec: &Self::Record) -> Vec<Value>;
+}
+
+pub trait TableStorage<T: Table> {
+    fn load(&self, table: &mut T) -> Result<()>;
+    // writes what changed since loaded or last staged, to be made durable
+    // by `Storage::commit()`
+    fn stage(&self, table: &mut T) -> Result<()>;
+}
+
+pub trait Storage: TableStorage<PhotoTable> + TableStorage<MountPointTable> {
+    // PIDs of the photos matching the filter, with the changes of the
+    // table in memory taken into account
+    fn select(&self, photos: &PhotoTable, filter: &IndexedFilter) -> Result<BTreeSet<PID>>;
+    // makes the tables staged durable, along with the journal of the files
+    // staged besides them
+    fn commit(&self, journal: &[(PathBuf, PathBuf)]) -> Result<()>;
+    fn discard(&self);
+    // the journals committed but not yet rolled forward
+    fn journals(&self) -> Result<Vec<Vec<(PathBuf, PathBuf)>>>;
+    fn clear_journals(&self) -> Result<()>;
+}
@@ -0,0 +1,400 @@
+use super::*;
+use crate::db::journal;
+use crate::db::photos::sql_status;
+use crate::util::serde::{migrate, TableIO};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::cell::RefCell;
+use std::ops::Bound;
+
+// Tables kept in one SQLite database. Each record is a row holding its
+// bincode entry (key and record, as in the table's map) next to indexed
+// columns. What precedes the map in the table's bincode form is kept in
+// `meta`, so that the rows concatenate back into the same payload as a
+// bincode file, and the migrations of `Versioned` apply unchanged.
+//
+// Tables are read whole on load, as commands list and patch the records,
+// but only the rows of the records changed are written when staged, and
+// the filters on status, selection, mount point and time are answered by
+// the indexed columns. Records changed but not staged yet are matched in
+// memory instead.
+
+pub struct SqliteStorage {
+    path: PathBuf,
+    dir: PathBuf,
+    conn: Connection,
+    state: RefCell<State>,
+}
+
+#[derive(Default)]
+struct State {
+    in_transaction: bool,
+    // bincode files imported, renamed once committed
+    imported: Vec<PathBuf>,
+}
+
+// the version, the head and the entries of a stored table
+type Stored = (u32, Vec<u8>, Vec<Vec<u8>>);
+
+fn create_table<T: StoredTable>(conn: &Connection, drop: bool) -> Result<()> {
+    let mut sql = String::new();
+    if drop {
+        sql += &format!("DROP TABLE IF EXISTS {};", T::NAME);
+    }
+    sql += &format!(
+        "CREATE TABLE IF NOT EXISTS {} (key {} PRIMARY KEY, entry BLOB NOT NULL",
+        T::NAME,
+        T::KEY_TYPE
+    );
+    for (name, ty) in T::COLUMNS {
+        sql += &format!(", {} {}", name, ty);
+    }
+    sql += ");";
+    for (name, _) in T::COLUMNS {
+        sql += &format!(
+            "CREATE INDEX IF NOT EXISTS {0}_{1} ON {0} ({1});",
+            T::NAME,
+            name
+        );
+    }
+    conn.execute_batch(&sql)?;
+    Ok(())
+}
+
+// what precedes the map in the bincode form of the table
+fn head_of<T>(table: &mut T) -> Result<Vec<u8>>
+where
+    T: StoredTable,
+{
+    let map = mem::take(table.split_mut().0);
+    let bytes = bincode::serialize(&*table);
+    *table.split_mut().0 = map;
+    let mut bytes = bytes?;
+    // the length of the empty map
+    bytes.truncate(bytes.len() - mem::size_of::<u64>());
+    Ok(bytes)
+}
+
+impl SqliteStorage {
+    // `dir` holds the database, and the bincode files to import
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let path = dir.join("butler.sqlite");
+        let conn = Connection::open(&path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS meta (
+                name TEXT PRIMARY KEY,
+                version INTEGER NOT NULL,
+                head BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS journal (entries BLOB NOT NULL);",
+        )?;
+        Ok(Self {
+            path,
+            dir: dir.into(),
+            conn,
+            state: Default::default(),
+        })
+    }
+    fn begin(&self) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        if !state.in_transaction {
+            self.conn.execute_batch("BEGIN IMMEDIATE")?;
+            state.in_transaction = true;
+        }
+        Ok(())
+    }
+    fn read<T: StoredTable>(&self) -> Result<Option<Stored>> {
+        let meta = self
+            .conn
+            .query_row(
+                "SELECT version, head FROM meta WHERE name = ?",
+                [T::NAME],
+                |row| Ok((row.get::<_, u32>(0)?, row.get::<_, Vec<u8>>(1)?)),
+            )
+            .optional()?;
+        let (version, head) = unwrap_some_or!(meta, { return Ok(None) });
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT entry FROM {}", T::NAME))?;
+        let entries = stmt
+            .query_map([], |row| row.get::<_, Vec<u8>>(0))?
+            .collect::<StdResult<Vec<_>, _>>()?;
+        Ok(Some((version, head, entries)))
+    }
+}
+
+impl<T> TableStorage<T> for SqliteStorage
+where
+    T: StoredTable,
+    T::PrimaryKey: Serialize,
+    T::Record: Serialize,
+{
+    fn load(&self, table: &mut T) -> Result<()> {
+        let (version, head, entries) = unwrap_some_or!(self.read::<T>()?, {
+            create_table::<T>(&self.conn, false)?;
+            let legacy = self.dir.join(T::NAME);
+            table.load_from_path(&legacy)?;
+            // not stored yet, meta included
+            table.changes_mut().touch_all();
+            if legacy.exists() {
+                info!(
+                    "Importing {} into {}",
+                    legacy.display(),
+                    self.path.display()
+                );
+                // written right away, for the file to be renamed only by the
+                // commit holding its rows, whether the table is staged or not
+                self.stage(table)?;
+                *table.changes_mut() = Default::default();
+                self.state.borrow_mut().imported.push(legacy);
+            }
+            return Ok(());
+        });
+        if version > T::VERSION {
+            bail!(
+                "table {} in {} is of schema version {}, but only {} is supported, upgrade butler",
+                T::NAME,
+                self.path.display(),
+                version,
+                T::VERSION
+            );
+        }
+
+        let mut payload = head;
+        payload.extend((entries.len() as u64).to_le_bytes());
+        entries.iter().for_each(|entry| payload.extend(entry));
+        let migrated = match version < T::VERSION {
+            true => Some(migrate::<T>(&self.path, version, &payload)?),
+            false => None,
+        };
+        let desered: T = bincode::deserialize(migrated.as_deref().unwrap_or(&payload))
+            .map_err(|e| anyhow!("Error reading table {}: {}", T::NAME, e))?;
+        *table = desered;
+        if migrated.is_some() {
+            // rewritten as a whole, as the columns may have changed too
+            table.changes_mut().touch_all();
+        }
+        Ok(())
+    }
+
+    // writes the changed rows, within a transaction ended by commit() or discard()
+    fn stage(&self, table: &mut T) -> Result<()> {
+        if table.changes().is_empty() {
+            debug!("Table {} unmodified, will not save", T::NAME);
+            return Ok(());
+        }
+        self.begin()?;
+        let all = table.changes().all;
+        create_table::<T>(&self.conn, all)?;
+        if all || table.changes().head {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO meta (name, version, head) VALUES (?, ?, ?)",
+                params![T::NAME, T::VERSION, head_of(table)?],
+            )?;
+        }
+
+        let mut upsert = self.conn.prepare(&format!(
+            "INSERT OR REPLACE INTO {} (key, entry{}) VALUES (?{})",
+            T::NAME,
+            T::COLUMNS
+                .iter()
+                .map(|(name, _)| format!(", {}", name))
+                .collect::<String>(),
+            ", ?".repeat(T::COLUMNS.len() + 1),
+        ))?;
+        let mut delete = self
+            .conn
+            .prepare(&format!("DELETE FROM {} WHERE key = ?", T::NAME))?;
+        let map = table.treemap();
+        let changes = table.changes();
+        let keys: Box<dyn Iterator<Item = &T::PrimaryKey>> = match all {
+            true => Box::new(map.keys()),
+            false => Box::new(changes.keys.iter()),
+        };
+        let (mut written, mut deleted) = (0, 0);
+        for key in keys {
+            match map.get(key) {
+                Some(rec) => {
+                    let entry = bincode::serialize(&(key, rec))?;
+                    let mut values = vec![T::sql_key(key), Value::Blob(entry)];
+                    values.extend(T::sql_columns(rec));
+                    upsert.execute(params_from_iter(values))?;
+                    written += 1;
+                }
+                // removed from the table
+                None => {
+                    delete.execute([T::sql_key(key)])?;
+                    deleted += 1;
+                }
+            }
+        }
+        debug!(
+            "Table {}: {} rows written, {} deleted",
+            T::NAME,
+            written,
+            deleted
+        );
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn select(&self, photos: &PhotoTable, filter: &IndexedFilter) -> Result<BTreeSet<PID>> {
+        let changes = photos.changes();
+        if changes.all {
+            return Ok(filter.eval_in(photos));
+        }
+        // in seconds, as the bounds of days are
+        let bound = |x: Bound<&DateTime<Utc>>, included, excluded| match x {
+            Bound::Included(t) => Some((format!("etime {} ?", included), t.timestamp())),
+            Bound::Excluded(t) => Some((format!("etime {} ?", excluded), t.timestamp())),
+            Bound::Unbounded => None,
+        };
+        let mut conds = vec![];
+        let mut values = vec![];
+        match filter {
+            IndexedFilter::Status(status) => {
+                conds.push("status = ?".to_owned());
+                values.push(Value::Text(sql_status(*status).to_owned()));
+            }
+            IndexedFilter::Selected => conds.push("selected = 1".to_owned()),
+            IndexedFilter::Mount(mpid) => {
+                conds.push("mpid = ?".to_owned());
+                values.push(Value::Text(mpid.to_string()));
+            }
+            IndexedFilter::Taken(start, end) => {
+                conds.push("etime IS NOT NULL".to_owned());
+                let bounds = bound(start.as_ref(), ">=", ">").into_iter().chain(bound(
+                    end.as_ref(),
+                    "<=",
+                    "<",
+                ));
+                for (cond, t) in bounds {
+                    conds.push(cond);
+                    values.push(Value::Integer(t));
+                }
+            }
+        }
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT key FROM {} WHERE {}",
+            PhotoTable::NAME,
+            conds.join(" AND ")
+        ))?;
+        let mut pids = stmt
+            .query_map(params_from_iter(values), |row| row.get::<_, PID>(0))?
+            .collect::<StdResult<BTreeSet<_>, _>>()?;
+        // the rows of records changed since staged are stale
+        for pid in changes.keys.iter() {
+            pids.remove(pid);
+        }
+        pids.extend(
+            photos
+                .records_of(changes.keys.iter().cloned())
+                .filter_map(|rec| match filter.matches(rec) {
+                    true => Some(rec.pid),
+                    false => None,
+                }),
+        );
+        Ok(pids)
+    }
+
+    // commits the rows written along with the journal of the files staged
+    // besides them, so that one COMMIT decides both
+    fn commit(&self, journal: &[(PathBuf, PathBuf)]) -> Result<()> {
+        if !journal.is_empty() {
+            self.begin()?;
+            self.conn.execute(
+                "INSERT INTO journal (entries) VALUES (?)",
+                [bincode::serialize(journal)?],
+            )?;
+        }
+        let mut state = self.state.borrow_mut();
+        if state.in_transaction {
+            self.conn.execute_batch("COMMIT")?;
+            state.in_transaction = false;
+        }
+        for legacy in state.imported.drain(..) {
+            let renamed = PathBuf::from(format!("{}.imported", legacy.display()));
+            fs::rename(&legacy, &renamed)?;
+            info!(
+                "{} imported, renamed to {}",
+                legacy.display(),
+                renamed.display()
+            );
+        }
+        Ok(())
+    }
+
+    fn discard(&self) {
+        let mut state = self.state.borrow_mut();
+        if state.in_transaction {
+            let _ = self.conn.execute_batch("ROLLBACK");
+            state.in_transaction = false;
+        }
+        state.imported.clear();
+    }
+
+    // also the one left by the bincode storage, if switched from it
+    fn journals(&self) -> Result<Vec<Vec<(PathBuf, PathBuf)>>> {
+        let mut journals = journal::read_file_journal()?
+            .into_iter()
+            .collect::<Vec<_>>();
+        let mut stmt = self.conn.prepare("SELECT entries FROM journal")?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, Vec<u8>>(0))?
+            .collect::<StdResult<Vec<_>, _>>()?;
+        for bytes in rows.iter() {
+            journals.push(bincode::deserialize(bytes).map_err(|e| {
+                anyhow!(
+                    "Error reading the journal in {}: {}",
+                    self.path.display(),
+                    e
+                )
+            })?);
+        }
+        Ok(journals)
+    }
+
+    fn clear_journals(&self) -> Result<()> {
+        journal::remove_file_journal()?;
+        self.conn.execute("DELETE FROM journal", [])?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_import_committed_unstaged() -> Result<()> {
+    let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+    fs::create_dir_all(&dir)?;
+    let path = CanonicalizedPath::new(&dir)?;
+    let mut mounts = MountPointTable::new();
+    mounts.insert(MountPointRecord {
+        uuid: Uuid::new_v4(),
+        path: path.clone(),
+        alias: Some("tmp".into()),
+        utc_offset: Default::default(),
+        offline: false,
+        volume_id: None,
+        rescan: false,
+    });
+    mounts.save_to_path(dir.join(MountPointTable::NAME))?;
+
+    // loaded by a command leaving the table as it is
+    let storage = SqliteStorage::open(&dir)?;
+    let mut loaded = MountPointTable::new();
+    storage.load(&mut loaded)?;
+    assert!(loaded.changes().is_empty());
+    Storage::commit(&storage, &[])?;
+    drop(storage);
+
+    let storage = SqliteStorage::open(&dir)?;
+    let mut loaded = MountPointTable::new();
+    storage.load(&mut loaded)?;
+    assert_eq!(loaded.treemap()[&path].alias.as_deref(), Some("tmp"));
+    assert!(!dir.join(MountPointTable::NAME).exists());
+    assert!(dir.join("mountpoints.imported").exists());
+    drop(storage);
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
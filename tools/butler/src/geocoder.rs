@@ -0,0 +1,129 @@
+use crate::config::config;
+use crate::prelude::*;
+use std::lazy::SyncOnceCell;
+
+// Offline reverse geocoding with a GeoNames cities file, e.g.
+// cities15000.txt from https://download.geonames.org/export/dump/, whose
+// tab-separated columns are described in the readme there.
+
+// farther from any city, a photo has no place
+const MAX_DISTANCE_KM: f64 = 50.;
+const EARTH_RADIUS_KM: f64 = 6371.;
+
+#[derive(Debug)]
+pub struct Place {
+    pub name: String,
+    pub ascii_name: String,
+    pub country: String, // ISO-3166 alpha-2 code
+    latitude: f64,
+    longitude: f64,
+}
+
+impl Place {
+    // by its name or the code of its country, ignoring case
+    pub fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self.ascii_name.eq_ignore_ascii_case(name)
+            || self.country.eq_ignore_ascii_case(name)
+    }
+}
+
+pub struct Geocoder {
+    places: Vec<Place>,
+    // indices into places by the whole degrees of latitude and longitude
+    grid: HashMap<(i32, i32), Vec<usize>>,
+}
+
+fn cell(latitude: f64, longitude: f64) -> (i32, i32) {
+    (latitude.floor() as i32, longitude.floor() as i32)
+}
+
+fn distance_km((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (lon2 - lon1).to_radians();
+    let a = (dlat / 2.).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.).sin().powi(2);
+    2. * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+impl Geocoder {
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut places = vec![];
+        let mut grid = HashMap::<_, Vec<_>>::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let cols = line.split('\t').collect::<Vec<_>>();
+            let parsed = match cols[..] {
+                [_, name, ascii_name, _, lat, lon, _, _, country, ..] => f64::from_str(lat)
+                    .ok()
+                    .zip(f64::from_str(lon).ok())
+                    .map(|(latitude, longitude)| Place {
+                        name: name.to_owned(),
+                        ascii_name: ascii_name.to_owned(),
+                        country: country.to_owned(),
+                        latitude,
+                        longitude,
+                    }),
+                _ => None,
+            };
+            let place = unwrap_some_or!(parsed, { bail!("malformed line {}", i + 1) });
+            grid.entry(cell(place.latitude, place.longitude))
+                .or_default()
+                .push(places.len());
+            places.push(place);
+        }
+        Ok(Self { places, grid })
+    }
+
+    pub fn nearest(&self, pos: &GeoPosition) -> Option<&Place> {
+        let here = (pos.latitude(), pos.longitude());
+        let (row, col) = cell(here.0, here.1);
+        // cells narrow towards the poles, so more of them are searched
+        let cols = (1. / here.0.to_radians().cos().max(0.01)).ceil().min(180.) as i32;
+        (row - 1..=row + 1)
+            .flat_map(|r| (col - cols..=col + cols).map(move |c| (r, (c + 540) % 360 - 180)))
+            .filter_map(|key| self.grid.get(&key))
+            .flatten()
+            .map(|&i| {
+                let place = &self.places[i];
+                (distance_km(here, (place.latitude, place.longitude)), place)
+            })
+            .filter(|(d, _)| *d <= MAX_DISTANCE_KM)
+            .min_by(|x, y| x.0.total_cmp(&y.0))
+            .map(|(_, place)| place)
+    }
+}
+
+static GEOCODER: SyncOnceCell<StdResult<Geocoder, String>> = SyncOnceCell::new();
+
+// fails if no GeoNames file is configured or it cannot be read
+pub fn geocoder() -> Result<&'static Geocoder> {
+    GEOCODER
+        .get_or_init(|| {
+            let path = config().geonames.as_ref().ok_or_else(|| {
+                "no GeoNames file in config.json, see notes/locations.bin.md".to_owned()
+            })?;
+            File::open(path)
+                .map_err(Error::new)
+                .and_then(|file| Geocoder::from_reader(BufReader::new(file)))
+                .map_err(|e| format!("Error reading {}: {}", path.display(), e))
+        })
+        .as_ref()
+        .map_err(|e| anyhow!("{}", e))
+}
+
+#[test]
+fn test_geocoder_nearest() -> Result<()> {
+    let cities = "\
+1796236\tShanghai\tShanghai\t\t31.22222\t121.45806\tP\tPPLA\tCN\t\t23\t\t\t\t24874500\t\t10\tAsia/Shanghai\t2021-05-01
+2988507\tParis\tParis\t\t48.85341\t2.3488\tP\tPPLC\tFR\t\t11\t75\t751\t75056\t2138551\t\t42\tEurope/Paris\t2020-05-26
+";
+    let geocoder = Geocoder::from_reader(cities.as_bytes())?;
+    let at = |lat, lon| GeoPosition::from_degrees(lat, lon, None).unwrap();
+    let place = geocoder.nearest(&at(31.1, 121.3)).unwrap();
+    assert!(place.matches("shanghai") && place.matches("CN"));
+    assert_eq!(geocoder.nearest(&at(48.9, 2.2)).unwrap().name, "Paris");
+    assert!(geocoder.nearest(&at(40., 116.)).is_none());
+    assert!(Geocoder::from_reader("garbage".as_bytes()).is_err());
+    Ok(())
+}
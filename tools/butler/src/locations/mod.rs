@@ -7,8 +7,16 @@ use crate::prelude::*;
 pub struct CanonicalizedPath(Arc<Path>);
 
 impl CanonicalizedPath {
-    pub fn new<P: AsRef<Path>>(path: P) -> Self {
-        Self(path.as_ref().canonicalize().unwrap().into())
+    // for paths that were canonicalized before, e.g. ones of offline mount points
+    pub fn new_unchecked<P: AsRef<Path>>(path: P) -> Self {
+        Self(path.as_ref().into())
+    }
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("cannot resolve {}", path.display()))?;
+        Ok(Self(path.into()))
     }
 }
 
@@ -19,11 +27,11 @@ impl fmt::Display for CanonicalizedPath {
 }
 
 pub trait Canonicalize {
-    fn resolve(self) -> CanonicalizedPath;
+    fn resolve(self) -> Result<CanonicalizedPath>;
 }
 
 impl<P: AsRef<Path>> Canonicalize for P {
-    fn resolve(self) -> CanonicalizedPath {
+    fn resolve(self) -> Result<CanonicalizedPath> {
         CanonicalizedPath::new(self)
     }
 }
@@ -41,20 +49,6 @@ pub struct DirectoryLocation {
     pub path: CanonicalizedPath,
 }
 
-impl DirectoryLocation {
-    pub fn from_mpid(mpid: Uuid) -> Option<Self> {
-        mpt_access().query(mpid).map(Self::from)
-    }
-
-    pub fn from_path(path: &CanonicalizedPath) -> Option<Self> {
-        mpt_access().query(path.clone()).map(Self::from)
-    }
-    fn from_path_unchecked<P: AsRef<Path>>(path: P) -> Option<Self> {
-        let path = CanonicalizedPath(path.as_ref().into());
-        Self::from_path(&path)
-    }
-}
-
 impl From<&MountPointRecord> for DirectoryLocation {
     fn from(mp: &MountPointRecord) -> Self {
         Self {
@@ -83,29 +77,52 @@ pub struct FileLocation {
 }
 
 impl FileLocation {
+    pub fn new<P: AsRef<Path>>(mpid: Uuid, filename: P) -> Self {
+        Self {
+            mpid,
+            filename: filename.as_ref().into(),
+            fullpath_cache: SyncOnceCell::new(),
+        }
+    }
     fn get_parts(&self) -> (Uuid, Arc<Path>) {
         (self.mpid, self.filename.clone())
     }
-    pub fn filepath(&self) -> &Path {
-        self.fullpath_cache.get_or_init(|| {
-            DirectoryLocation::from_mpid(self.mpid)
-                .unwrap()
-                .path
-                .join(&self.filename)
-        })
+    pub fn filepath(&self) -> Result<&Path> {
+        self.try_filepath()
+            .ok_or_else(|| anyhow!("mount point of {} is gone", self))
+    }
+    // None if the mount point has been removed
+    pub fn try_filepath(&self) -> Option<&Path> {
+        self.fullpath_cache.get().map(|path| path.as_path())
     }
-    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let path = path.as_ref().canonicalize()?;
+    // remembers the directory of the mount point as loaded
+    pub fn locate(&self, dir: &Path) {
+        self.fullpath_cache.get_or_init(|| dir.join(&self.filename));
+    }
+    // `path` is canonicalized, on one of the mount points
+    pub fn from_path<P: AsRef<Path>>(path: P, mounts: &MountPointTable) -> Result<Self> {
+        let path = path.as_ref();
         let dir = path
             .parent()
             .ok_or_else(|| anyhow!("cannot get parent for {}", path.display()))?;
         let filename = path
             .file_name()
             .ok_or_else(|| anyhow!("cannot get file name for {}", path.display()))?;
-        let this = DirectoryLocation::from_path_unchecked(&dir)
-            .ok_or_else(|| anyhow!("{} not mounted", dir.display()))?
-            .with_filename(filename);
-        Ok(this)
+        let sel = CanonicalizedPath::new_unchecked(dir).into();
+        let mp = mounts
+            .resolve(&sel)
+            .map_err(|_| anyhow!("{} not mounted", dir.display()))?;
+        Ok(DirectoryLocation::from(mp).with_filename(filename))
+    }
+}
+
+// the full path, or the MPID and file name if the mount point is gone
+impl fmt::Display for FileLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_filepath() {
+            Some(path) => write!(f, "{}", path.display()),
+            None => write!(f, "{}:{}", self.mpid, self.filename.display()),
+        }
     }
 }
 
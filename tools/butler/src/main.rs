@@ -11,8 +11,10 @@ extern crate num_cpus;
 
 mod _vendors;
 mod commands;
+mod config;
 mod consts;
 mod db;
+mod geocoder;
 mod locations;
 mod prelude;
 mod util;
@@ -38,7 +40,12 @@ fn goto_work_directory() -> Result<()> {
 
 fn main() -> Result<()> {
     goto_work_directory()?;
-    mpt_access_mut().initialize(DEFAULT_MOUNTPOINTS_DB_PATH)?;
-    commands::handle_cli()?;
-    mpt_access().finalize(DEFAULT_MOUNTPOINTS_DB_PATH)
+    config::load()?;
+    lockfile::acquire()?;
+    let mut db = Database::open()?;
+    if let Err(e) = commands::handle_cli(&mut db) {
+        db.discard();
+        return Err(e);
+    }
+    db.commit()
 }
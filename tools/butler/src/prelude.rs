@@ -10,7 +10,7 @@ pub use serde::ser::{SerializeMap, SerializeSeq};
 pub use serde::{Deserialize, Serialize};
 pub use std::borrow::Borrow;
 pub use std::cell::RefCell;
-pub use std::collections::{BTreeMap, HashMap, HashSet};
+pub use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 pub use std::env;
 pub use std::ffi::{OsStr, OsString};
 pub use std::fmt;
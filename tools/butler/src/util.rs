@@ -21,44 +21,138 @@ pub mod serde {
 
     use crate::prelude::*;
 
+    // upgrades a payload of schema version `from` to `from + 1`
+    pub struct Migration {
+        pub from: u32,
+        pub run: fn(&[u8]) -> Result<Vec<u8>>,
+    }
+
+    // Files start with MAGIC and VERSION (u32, little endian), followed by
+    // the bincode payload. Files written before versioning have no header
+    // and are taken as LEGACY_VERSION.
+    pub trait Versioned {
+        const MAGIC: &'static [u8; 4];
+        const VERSION: u32;
+        const LEGACY_VERSION: u32 = 0;
+        const MIGRATIONS: &'static [Migration];
+    }
+
+    // decodes a frozen snapshot of one version and encodes it as the next one
+    pub fn migrate_with<A, B, F>(payload: &[u8], f: F) -> Result<Vec<u8>>
+    where
+        A: serde::de::DeserializeOwned,
+        B: Serialize,
+        F: FnOnce(A) -> B,
+    {
+        Ok(bincode::serialize(&f(bincode::deserialize(payload)?))?)
+    }
+
+    fn split_header<T: Versioned>(bytes: &[u8]) -> (u32, &[u8]) {
+        match bytes.strip_prefix(T::MAGIC) {
+            Some(rest) if rest.len() >= 4 => {
+                let (version, payload) = rest.split_at(4);
+                (u32::from_le_bytes(version.try_into().unwrap()), payload)
+            }
+            _ => (T::LEGACY_VERSION, bytes),
+        }
+    }
+
+    // backs up the file holding the table, then runs the migrations
+    pub fn migrate<T: Versioned>(path: &Path, version: u32, payload: &[u8]) -> Result<Vec<u8>> {
+        let backup = PathBuf::from(format!("{}.v{}.bak", path.display(), version));
+        fs::copy(path, &backup).with_context(|| format!("cannot back up {}", path.display()))?;
+        info!(
+            "Migrating {} from schema version {} to {}, backed up to {}",
+            path.display(),
+            version,
+            T::VERSION,
+            backup.display()
+        );
+        let mut payload = payload.to_vec();
+        for from in version..T::VERSION {
+            let migration = T::MIGRATIONS
+                .iter()
+                .find(|m| m.from == from)
+                .ok_or_else(|| anyhow!("no migration from schema version {}", from))?;
+            payload = (migration.run)(&payload).with_context(|| {
+                format!(
+                    "cannot migrate {} from schema version {}",
+                    path.display(),
+                    from
+                )
+            })?;
+        }
+        Ok(payload)
+    }
+
+    // decodes the bytes of a file holding T, migrated to the current schema
+    // if need be, with whether it was
+    pub fn decode_versioned<T>(path: &Path, bytes: &[u8]) -> Result<(T, bool)>
+    where
+        T: Versioned + serde::de::DeserializeOwned,
+    {
+        let (version, payload) = split_header::<T>(bytes);
+        if version > T::VERSION {
+            bail!(
+                "{} is of schema version {}, but only {} is supported, upgrade butler",
+                path.display(),
+                version,
+                T::VERSION
+            );
+        }
+        let migrated = match version < T::VERSION {
+            true => Some(migrate::<T>(path, version, payload)?),
+            false => None,
+        };
+        let desered = bincode::deserialize(migrated.as_deref().unwrap_or(payload))
+            .map_err(|e| anyhow!("Error reading {}: {}", path.display(), e))?;
+        Ok((desered, migrated.is_some()))
+    }
+
+    // writes T with its header, atomically
+    pub fn save_versioned<T: Versioned + Serialize>(value: &T, path: &Path) -> Result<()> {
+        use atomicwrites::{AllowOverwrite, AtomicFile, Error as AtomicFileError};
+
+        AtomicFile::new(path, AllowOverwrite)
+            .write(|file| -> StdResult<(), _> {
+                let mut writer = BufWriter::new(file);
+                writer.write_all(T::MAGIC)?;
+                writer.write_all(&T::VERSION.to_le_bytes())?;
+                bincode::serialize_into(writer, value)
+            })
+            .map_err(|e| match e {
+                AtomicFileError::Internal(e) => e.into(),
+                AtomicFileError::User(e) => e.into(),
+            })
+    }
+
     pub trait TableIO<'a> {
         fn load_from_path<P: AsRef<Path>>(&'a mut self, path: P) -> Result<()>;
         fn save_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()>;
     }
 
-    impl<'a, T: serde::de::DeserializeOwned + Serialize + Table> TableIO<'a> for T {
+    impl<'a, T: serde::de::DeserializeOwned + Serialize + Table + Versioned> TableIO<'a> for T {
         fn load_from_path<P: AsRef<Path>>(&'a mut self, path: P) -> Result<()> {
             let path = path.as_ref();
             if !path.exists() {
                 fs::create_dir_all(path.parent().unwrap())?;
                 return Ok(());
             }
-            let file = File::open(path)?;
-            let reader = BufReader::new(file);
-            let desered: T = bincode::deserialize_from(reader)
-                .map_err(|e| anyhow!("Error reading {}: {}", path.display(), e))?;
+            let (desered, migrated) = decode_versioned::<T>(path, &fs::read(path)?)?;
             mem::drop(mem::replace(self, desered));
+            if migrated {
+                // so that it is written back in the new schema
+                self.changes_mut().touch_all();
+            }
             Ok(())
         }
         fn save_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-            use atomicwrites::{AllowOverwrite, AtomicFile, Error as AtomicFileError};
-
-            let modified = self.modified_flag().get();
             let path = path.as_ref();
-            if !modified && path.exists() {
+            if self.changes().is_empty() && path.exists() {
                 debug!("Table unmodified, will not save to {}", path.display());
                 return Ok(());
             }
-
-            AtomicFile::new(path, AllowOverwrite)
-                .write(|file| -> StdResult<(), _> {
-                    let writer = BufWriter::new(file);
-                    bincode::serialize_into(writer, self)
-                })
-                .map_err(|e| match e {
-                    AtomicFileError::Internal(e) => e.into(),
-                    AtomicFileError::User(e) => e.into(),
-                })
+            save_versioned(self, path)
         }
     }
 }
@@ -99,16 +193,165 @@ pub mod exif {
         })
     }
 
-    pub fn read_datetime(exif: &exif::Exif) -> Result<Option<DateTime<Utc>>> {
-        let ret = read_string(&exif, exif::Tag::DateTime)?
-            .and_then(|time_str| NaiveDateTime::parse_from_str(&time_str, "%Y:%m:%d %H:%M:%S").ok())
-            .and_then(|dt| {
-                FixedOffset::east(8 * 60 * 60)
-                    .from_local_datetime(&dt)
-                    .single()
-            })
-            .map(DateTime::<Utc>::from);
-        Ok(ret)
+    // trimmed and non-empty, as makers pad strings with spaces or NULs
+    pub fn read_text(exif: &exif::Exif, tag: exif::Tag) -> Option<String> {
+        read_string(exif, tag)
+            .ok()
+            .flatten()
+            .map(|s| trim_ascii(&s).to_owned())
+            .filter(|s| !s.is_empty())
+    }
+
+    pub fn read_rational(exif: &exif::Exif, tag: exif::Tag) -> Option<exif::Rational> {
+        exif.get_field(tag, exif::In::PRIMARY)
+            .and_then(match_exif_value!(exif::Value::Rational))
+            .and_then(|v| v.first().cloned())
+            .filter(|x| x.denom != 0)
+    }
+
+    fn trim_ascii(s: &str) -> &str {
+        s.trim_matches(|c: char| c.is_whitespace() || c == '\0')
+    }
+
+    // parses offsets like "+08:00" or "-05:30", as in OffsetTime* tags
+    pub fn parse_offset(s: &str) -> Option<FixedOffset> {
+        let s = trim_ascii(s);
+        let sign = match s.chars().next()? {
+            '+' => 1,
+            '-' => -1,
+            _ => return None,
+        };
+        let (h, m) = s[1..].split_once(':').unwrap_or((&s[1..], "0"));
+        let (h, m) = (i32::from_str(h).ok()?, i32::from_str(m).ok()?);
+        if !(0..=14).contains(&h) || !(0..=59).contains(&m) {
+            return None;
+        }
+        let secs = sign * (h * 3600 + m * 60);
+        // offsets in use range from -12:00 to +14:00
+        if !(-12 * 3600..=14 * 3600).contains(&secs) {
+            return None;
+        }
+        FixedOffset::east_opt(secs)
+    }
+
+    // parses SubSecTime* tags, "123" stands for 0.123s
+    fn parse_subsec(s: &str) -> Option<u32> {
+        let s = trim_ascii(s);
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let nanos = format!("{:0<9}", &s[..s.len().min(9)]);
+        u32::from_str(&nanos).ok()
+    }
+
+    // GPS time is in UTC, so the difference to local time is the offset, which
+    // should be a multiple of 15min. A stale GPS fix yields a non-multiple.
+    fn infer_offset(local: &NaiveDateTime, utc: &DateTime<Utc>) -> Option<FixedOffset> {
+        const QUARTER: i64 = 15 * 60;
+        let diff = (*local - utc.naive_utc()).num_seconds();
+        let rounded = (diff as f64 / QUARTER as f64).round() as i64 * QUARTER;
+        if (diff - rounded).abs() > 5 * 60 || rounded.abs() > 14 * 60 * 60 {
+            return None;
+        }
+        FixedOffset::east_opt(rounded as i32)
+    }
+
+    fn read_naive_datetime(
+        exif: &exif::Exif,
+        tag: exif::Tag,
+        subsec_tag: exif::Tag,
+    ) -> Result<Option<NaiveDateTime>> {
+        let dt = read_string(&exif, tag)?.and_then(|time_str| {
+            NaiveDateTime::parse_from_str(trim_ascii(&time_str), "%Y:%m:%d %H:%M:%S").ok()
+        });
+        let nanos = read_string(&exif, subsec_tag)?
+            .as_deref()
+            .and_then(parse_subsec);
+        Ok(dt.map(|dt| nanos.and_then(|n| dt.with_nanosecond(n)).unwrap_or(dt)))
+    }
+
+    pub fn read_gps_datetime(exif: &exif::Exif) -> Result<Option<DateTime<Utc>>> {
+        let date = read_string(&exif, exif::Tag::GPSDateStamp)?
+            .and_then(|date_str| NaiveDate::parse_from_str(trim_ascii(&date_str), "%Y:%m:%d").ok());
+        let time = exif
+            .get_field(exif::Tag::GPSTimeStamp, exif::In::PRIMARY)
+            .and_then(match_exif_value!(exif::Value::Rational))
+            .filter(|v| v.len() >= 3)
+            .and_then(|v| {
+                let secs = v[2].to_f64();
+                NaiveTime::from_hms_nano_opt(
+                    v[0].to_f64() as u32,
+                    v[1].to_f64() as u32,
+                    secs.trunc() as u32,
+                    (secs.fract() * 1e9) as u32,
+                )
+            });
+        Ok(date
+            .zip(time)
+            .map(|(d, t)| DateTime::<Utc>::from_utc(d.and_time(t), Utc)))
+    }
+
+    /// Reads the time the photo was taken. The offset is taken from the
+    /// matching OffsetTime* tag, or inferred from the GPS timestamp, and
+    /// `default_tz` is used only if both are absent.
+    pub fn read_datetime(
+        exif: &exif::Exif,
+        default_tz: FixedOffset,
+    ) -> Result<Option<DateTime<Utc>>> {
+        use exif::Tag;
+        const TAGS: [(Tag, Tag, Tag); 3] = [
+            (
+                Tag::DateTimeOriginal,
+                Tag::OffsetTimeOriginal,
+                Tag::SubSecTimeOriginal,
+            ),
+            (
+                Tag::DateTimeDigitized,
+                Tag::OffsetTimeDigitized,
+                Tag::SubSecTimeDigitized,
+            ),
+            (Tag::DateTime, Tag::OffsetTime, Tag::SubSecTime),
+        ];
+
+        let gps = read_gps_datetime(&exif)?;
+        for (tag, offset_tag, subsec_tag) in TAGS {
+            let local = unwrap_some_or!(read_naive_datetime(&exif, tag, subsec_tag)?, {
+                continue;
+            });
+            let tz = read_string(&exif, offset_tag)?
+                .as_deref()
+                .and_then(parse_offset)
+                .or_else(|| gps.and_then(|gps| infer_offset(&local, &gps)))
+                .unwrap_or(default_tz);
+            return Ok(tz
+                .from_local_datetime(&local)
+                .single()
+                .map(DateTime::<Utc>::from));
+        }
+        Ok(gps)
+    }
+
+    #[test]
+    fn test_parse_time_parts() {
+        assert_eq!(parse_offset("+08:00"), FixedOffset::east_opt(8 * 3600));
+        assert_eq!(
+            parse_offset("-05:30"),
+            FixedOffset::east_opt(-5 * 3600 - 1800)
+        );
+        assert_eq!(parse_offset("   :  "), None);
+        assert_eq!(parse_offset("+9999999"), None);
+        assert_eq!(parse_offset("+15:00"), None);
+        assert_eq!(parse_offset("+08:60"), None);
+        assert_eq!(parse_offset("-12:00"), FixedOffset::west_opt(12 * 3600));
+        assert_eq!(parse_offset("-14:00"), None);
+        assert_eq!(parse_subsec("12"), Some(120_000_000));
+        assert_eq!(parse_subsec("  "), None);
+
+        let local = NaiveDate::from_ymd(2021, 5, 3).and_hms(20, 0, 10);
+        let gps = DateTime::<Utc>::from_utc(NaiveDate::from_ymd(2021, 5, 3).and_hms(11, 0, 0), Utc);
+        assert_eq!(infer_offset(&local, &gps), FixedOffset::east_opt(9 * 3600));
+        let stale = gps - chrono::Duration::minutes(38);
+        assert_eq!(infer_offset(&local, &stale), None);
     }
 
     // https://stackoverflow.com/a/48488655/3278171
@@ -116,12 +359,17 @@ pub mod exif {
         reader.rewind()?;
         let mut buf: Vec<u8> = vec![];
         reader.read_to_end(&mut buf)?;
+        let byte = |i: usize| -> Result<usize> {
+            buf.get(i)
+                .map(|&x| x as usize)
+                .ok_or_else(|| anyhow!("unexpected end of JPEG at offset {}", i))
+        };
         let mut offset = 0usize;
         while offset < buf.len() {
-            while buf[offset] == 0xff {
+            while byte(offset)? == 0xff {
                 offset += 1;
             }
-            let marker = buf[offset];
+            let marker = byte(offset)?;
             offset += 1;
             match marker {
                 0xd8 => continue, // SOI
@@ -130,15 +378,18 @@ pub mod exif {
                 0x01 => continue, // TEM
                 _ => (),
             }
-            let len = ((buf[offset] as usize) << 8) | buf[offset + 1] as usize;
+            let len = (byte(offset)? << 8) | byte(offset + 1)?;
             offset += 2;
 
             if marker == 0xc0 || marker == 0xc2 {
-                let h = ((buf[offset + 1] as u32) << 8) | (buf[offset + 2] as u32);
-                let w = ((buf[offset + 3] as u32) << 8) | (buf[offset + 4] as u32);
-                return Ok((w, h));
+                let h = (byte(offset + 1)? << 8) | byte(offset + 2)?;
+                let w = (byte(offset + 3)? << 8) | byte(offset + 4)?;
+                return Ok((w as u32, h as u32));
             }
 
+            if len < 2 {
+                bail!("invalid JPEG segment length {} at offset {}", len, offset);
+            }
             offset += len - 2;
         }
         Ok((0, 0))
@@ -157,6 +408,189 @@ pub mod exif {
     pub fn read_orientation(exif: &exif::Exif) -> u32 {
         read_u32(&exif, exif::Tag::Orientation).or(Some(1)).unwrap()
     }
+
+    // degrees, minutes and seconds, as in GPSLatitude and GPSLongitude
+    fn dms_to_degrees(dms: &[exif::Rational]) -> Option<f64> {
+        let parts = dms.iter().map(|x| x.to_f64()).collect::<Vec<_>>();
+        let degrees = match parts[..] {
+            [d, m, s] => d + m / 60. + s / 3600.,
+            [d, m] => d + m / 60.,
+            [d] => d,
+            _ => return None,
+        };
+        Some(degrees).filter(|x| x.is_finite())
+    }
+
+    fn read_coordinate(exif: &exif::Exif, tag: exif::Tag, ref_tag: exif::Tag) -> Option<f64> {
+        let degrees = exif
+            .get_field(tag, exif::In::PRIMARY)
+            .and_then(match_exif_value!(exif::Value::Rational))
+            .and_then(|v| dms_to_degrees(v))?;
+        let sign = match trim_ascii(&read_string(exif, ref_tag).ok()??) {
+            "N" | "E" => 1.,
+            "S" | "W" => -1.,
+            _ => return None,
+        };
+        Some(sign * degrees)
+    }
+
+    /// Reads latitude and longitude in degrees, with altitude in meters if
+    /// present. Photos without a GPS fix often carry zeros, taken as absent.
+    pub fn read_gps_position(exif: &exif::Exif) -> Option<(f64, f64, Option<f64>)> {
+        use exif::Tag;
+        let latitude = read_coordinate(exif, Tag::GPSLatitude, Tag::GPSLatitudeRef)?;
+        let longitude = read_coordinate(exif, Tag::GPSLongitude, Tag::GPSLongitudeRef)?;
+        if latitude == 0. && longitude == 0. {
+            return None;
+        }
+        let altitude = exif
+            .get_field(Tag::GPSAltitude, exif::In::PRIMARY)
+            .and_then(match_exif_value!(exif::Value::Rational))
+            .and_then(|v| v.first().map(|x| x.to_f64()))
+            .filter(|x| x.is_finite())
+            .map(|x| match read_u32(exif, Tag::GPSAltitudeRef) {
+                Some(1) => -x,
+                _ => x,
+            });
+        Some((latitude, longitude, altitude))
+    }
+
+    #[test]
+    fn test_dms_to_degrees() {
+        let r = |num, denom| exif::Rational { num, denom };
+        assert_eq!(dms_to_degrees(&[r(31, 1), r(30, 1), r(0, 1)]), Some(31.5));
+        assert_eq!(dms_to_degrees(&[r(121, 1), r(285, 10)]), Some(121.475));
+        assert_eq!(dms_to_degrees(&[r(1, 0)]), None);
+        assert_eq!(dms_to_degrees(&[]), None);
+    }
+}
+
+pub mod phash {
+    use image::imageops::FilterType;
+    use image::{DynamicImage, GrayImage};
+
+    // difference hash, see http://www.hackerfactor.com/blog/?/archives/529-Kind-of-Like-That.html
+    pub fn dhash(img: &DynamicImage) -> u64 {
+        let gray = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+        let mut hash = 0u64;
+        for y in 0..8 {
+            for x in 0..8 {
+                let left = gray.get_pixel(x, y).0[0];
+                let right = gray.get_pixel(x + 1, y).0[0];
+                hash = (hash << 1) | (left < right) as u64;
+            }
+        }
+        hash
+    }
+
+    pub fn hamming(x: u64, y: u64) -> u32 {
+        (x ^ y).count_ones()
+    }
+
+    fn laplacian_at(gray: &GrayImage, x: u32, y: u32) -> f64 {
+        let p = |x: u32, y: u32| gray.get_pixel(x, y).0[0] as f64;
+        4.0 * p(x, y) - p(x - 1, y) - p(x + 1, y) - p(x, y - 1) - p(x, y + 1)
+    }
+
+    pub fn laplacian_variance(img: &DynamicImage) -> f64 {
+        let gray = img.to_luma8();
+        let (w, h) = gray.dimensions();
+        if w < 3 || h < 3 {
+            return 0.0;
+        }
+        let (mut sum, mut sqsum) = (0f64, 0f64);
+        for y in 1..h - 1 {
+            for x in 1..w - 1 {
+                let v = laplacian_at(&gray, x, y);
+                sum += v;
+                sqsum += v * v;
+            }
+        }
+        let n = ((w - 2) * (h - 2)) as f64;
+        let mean = sum / n;
+        sqsum / n - mean * mean
+    }
+
+    #[test]
+    fn test_hamming() {
+        assert_eq!(hamming(0, 0), 0);
+        assert_eq!(hamming(0b1011, 0b0001), 2);
+        assert_eq!(hamming(u64::MAX, 0), 64);
+    }
+}
+
+pub mod time {
+    use crate::prelude::*;
+    use chrono::Duration;
+
+    // None if out of the range of Duration, where Duration::seconds panics
+    pub fn seconds(secs: i64) -> Option<Duration> {
+        let d = Duration::from_std(std::time::Duration::from_secs(secs.unsigned_abs())).ok()?;
+        Some(if secs < 0 { -d } else { d })
+    }
+
+    // parses offsets like "+1h30m", "-2d" or "45s"
+    pub fn parse_duration(s: &str) -> Result<Duration> {
+        let (sign, body) = match s.chars().next() {
+            Some('-') => (-1, &s[1..]),
+            Some('+') => (1, &s[1..]),
+            _ => (1, s),
+        };
+        if body.is_empty() {
+            bail!("empty duration {}", s);
+        }
+        let mut secs = 0i64;
+        let mut num = String::new();
+        for c in body.chars() {
+            if c.is_ascii_digit() {
+                num.push(c);
+                continue;
+            }
+            let unit = match c {
+                'd' => 24 * 60 * 60,
+                'h' => 60 * 60,
+                'm' => 60,
+                's' => 1,
+                _ => bail!("unknown unit {} in duration {}", c, s),
+            };
+            secs = i64::from_str(&num)
+                .ok()
+                .and_then(|n| n.checked_mul(unit))
+                .and_then(|n| n.checked_add(secs))
+                .ok_or_else(|| anyhow!("cannot parse duration {}", s))?;
+            num.clear();
+        }
+        if !num.is_empty() {
+            bail!("missing unit in duration {}", s);
+        }
+        seconds(sign * secs).ok_or_else(|| anyhow!("duration {} is out of range", s))
+    }
+
+    // parses RFC 3339 times, or local times like "2021-05-03T14:00:00" in `tz`
+    pub fn parse_time(s: &str, tz: &FixedOffset) -> Result<DateTime<Utc>> {
+        if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+            return Ok(t.into());
+        }
+        let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S")
+            .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S"))
+            .map_err(|_| anyhow!("cannot parse time {}", s))?;
+        tz.from_local_datetime(&naive)
+            .single()
+            .map(DateTime::<Utc>::from)
+            .ok_or_else(|| anyhow!("ambiguous time {}", s))
+    }
+
+    #[test]
+    fn test_parse_duration() -> Result<()> {
+        assert_eq!(parse_duration("+1h30m")?, Duration::minutes(90));
+        assert_eq!(parse_duration("-2d")?, Duration::days(-2));
+        assert_eq!(parse_duration("45s")?, Duration::seconds(45));
+        assert!(parse_duration("3").is_err());
+        assert!(parse_duration("1y").is_err());
+        assert!(parse_duration("999999999999999d").is_err());
+        assert!(parse_duration("9223372036854775807s").is_err());
+        Ok(())
+    }
 }
 
 pub mod math {
@@ -244,24 +678,7 @@ pub mod sync {
     use serde::de::Deserialize;
     use serde::ser::Serialize;
     use std::fmt;
-    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-
-    #[derive(Debug, Default)]
-    pub struct AtomicFlag(AtomicBool);
-
-    impl AtomicFlag {
-        pub fn new() -> Self {
-            Self(AtomicBool::new(false))
-        }
-
-        pub fn set(&self) {
-            self.0.store(true, Ordering::Relaxed);
-        }
-
-        pub fn get(&self) -> bool {
-            self.0.load(Ordering::Relaxed)
-        }
-    }
+    use std::sync::atomic::{AtomicU32, Ordering};
 
     #[derive(Debug)]
     pub struct AtomicCounter(AtomicU32);
@@ -386,6 +803,20 @@ pub mod functional {
     }
 }
 
+pub mod progress {
+    use indicatif::{ProgressBar, ProgressStyle};
+
+    pub fn bar(len: usize, msg: &'static str) -> ProgressBar {
+        let bar = ProgressBar::new(len as u64).with_style(
+            ProgressStyle::default_bar()
+                .template("{msg} [{bar:40}] {pos}/{len} ({per_sec}, ETA {eta})")
+                .progress_chars("=> "),
+        );
+        bar.set_message(msg);
+        bar
+    }
+}
+
 pub mod tabled {
     use std::fmt::Display;
     use tabled::{Alignment, Full, Modify, Style, Table, Tabled};